@@ -46,6 +46,18 @@ pub enum DTCStatus {
 }
 
 impl DTCStatus {
+    pub(crate) fn from_uds_status(x: DtcStatusMask) -> DTCStatus {
+        if x.is_confirmed_dtc() && x.is_test_failed() {
+            Self::Active
+        } else if x.is_confirmed_dtc() {
+            Self::Stored
+        } else if x.is_pending_dtc() || x.is_test_failed() {
+            Self::Pending
+        } else {
+            Self::None
+        }
+    }
+
     pub(crate) fn from_kwp_status(x: u8) -> DTCStatus {
         match (x & 0b01100000) >> 5 {
             0b00 => Self::None,
@@ -57,6 +69,132 @@ impl DTCStatus {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+/// UDS DTC status byte (ISO14229-1). This is used both as the status of a DTC,
+/// and as a mask for filtering DTCs by their status.
+///
+/// Masks can be built by combining the associated constants, for example
+/// `DtcStatusMask::PENDING_DTC | DtcStatusMask::CONFIRMED_DTC`
+pub struct DtcStatusMask(pub u8);
+
+impl DtcStatusMask {
+    /// The most recent test of the DTC failed
+    pub const TEST_FAILED: Self = Self(0x01);
+    /// A test of the DTC failed during the current operation cycle
+    pub const TEST_FAILED_THIS_OPERATION_CYCLE: Self = Self(0x02);
+    /// A test of the DTC failed during the current or last completed operation cycle
+    pub const PENDING_DTC: Self = Self(0x04);
+    /// The DTC is confirmed and stored in non volatile memory
+    pub const CONFIRMED_DTC: Self = Self(0x08);
+    /// The test of the DTC has not completed since DTCs were last cleared
+    pub const TEST_NOT_COMPLETED_SINCE_LAST_CLEAR: Self = Self(0x10);
+    /// A test of the DTC has failed at least once since DTCs were last cleared
+    pub const TEST_FAILED_SINCE_LAST_CLEAR: Self = Self(0x20);
+    /// The test of the DTC has not completed during the current operation cycle
+    pub const TEST_NOT_COMPLETED_THIS_OPERATION_CYCLE: Self = Self(0x40);
+    /// The ECU requests that a warning indicator (Such as the MIL) is active for the DTC
+    pub const WARNING_INDICATOR_REQUESTED: Self = Self(0x80);
+
+    /// Mask with no bits set
+    pub fn empty() -> Self {
+        Self(0x00)
+    }
+
+    /// Mask with every bit set
+    pub fn all() -> Self {
+        Self(0xFF)
+    }
+
+    /// Returns the mask with the bits of `other` set
+    pub fn with(self, other: Self) -> Self {
+        self | other
+    }
+
+    /// Returns the mask with the bits of `other` cleared
+    pub fn without(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Returns true if all the bits of `other` are set
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if any of the bits of `other` are set.
+    /// This is how the ECU matches the status of a DTC against a status mask
+    pub fn intersects(&self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns true if the testFailed bit is set
+    pub fn is_test_failed(&self) -> bool {
+        self.contains(Self::TEST_FAILED)
+    }
+
+    /// Returns true if the testFailedThisOperationCycle bit is set
+    pub fn is_test_failed_this_operation_cycle(&self) -> bool {
+        self.contains(Self::TEST_FAILED_THIS_OPERATION_CYCLE)
+    }
+
+    /// Returns true if the pendingDTC bit is set
+    pub fn is_pending_dtc(&self) -> bool {
+        self.contains(Self::PENDING_DTC)
+    }
+
+    /// Returns true if the confirmedDTC bit is set
+    pub fn is_confirmed_dtc(&self) -> bool {
+        self.contains(Self::CONFIRMED_DTC)
+    }
+
+    /// Returns true if the testNotCompletedSinceLastClear bit is set
+    pub fn is_test_not_completed_since_last_clear(&self) -> bool {
+        self.contains(Self::TEST_NOT_COMPLETED_SINCE_LAST_CLEAR)
+    }
+
+    /// Returns true if the testFailedSinceLastClear bit is set
+    pub fn is_test_failed_since_last_clear(&self) -> bool {
+        self.contains(Self::TEST_FAILED_SINCE_LAST_CLEAR)
+    }
+
+    /// Returns true if the testNotCompletedThisOperationCycle bit is set
+    pub fn is_test_not_completed_this_operation_cycle(&self) -> bool {
+        self.contains(Self::TEST_NOT_COMPLETED_THIS_OPERATION_CYCLE)
+    }
+
+    /// Returns true if the warningIndicatorRequested bit is set
+    pub fn is_warning_indicator_requested(&self) -> bool {
+        self.contains(Self::WARNING_INDICATOR_REQUESTED)
+    }
+}
+
+impl std::ops::BitOr for DtcStatusMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitAnd for DtcStatusMask {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl From<u8> for DtcStatusMask {
+    fn from(x: u8) -> Self {
+        Self(x)
+    }
+}
+
+impl From<DtcStatusMask> for u8 {
+    fn from(x: DtcStatusMask) -> Self {
+        x.0
+    }
+}
+
 /// Diagnostic trouble code (DTC) storage struct
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DTC {
@@ -73,9 +211,33 @@ pub struct DTC {
     pub mil_on: bool,
     /// Indication if the DTC conditions have been met since the last clear.
     pub readiness_flag: bool,
+    /// Raw status byte of the DTC as reported by the ECU. For UDS, this can be interpreted
+    /// using [DTC::get_uds_status]. For KWP2000, this is the KWP2000 status byte, which is
+    /// already decoded into [DTC::status], [DTC::mil_on] and [DTC::readiness_flag].
+    /// OBD-II does not report a status byte, so this is 0
+    pub raw_status: u8,
 }
 
 impl DTC {
+    /// Creates a DTC from its UDS status byte
+    pub(crate) fn from_uds(format: DTCFormatType, raw: u32, raw_status: u8) -> Self {
+        let status = DtcStatusMask(raw_status);
+        Self {
+            format,
+            raw,
+            status: DTCStatus::from_uds_status(status),
+            mil_on: status.is_warning_indicator_requested(),
+            readiness_flag: !status.is_test_not_completed_since_last_clear(),
+            raw_status,
+        }
+    }
+
+    /// Returns the status of the DTC as a UDS status byte. This is only meaningful
+    /// for DTCs read from a UDS ECU
+    pub fn get_uds_status(&self) -> DtcStatusMask {
+        DtcStatusMask(self.raw_status)
+    }
+
     /// Returns the error in a string format. EG: raw of 8276 = error P
     pub fn get_name_as_string(&self) -> String {
         match self.format {
@@ -127,8 +289,24 @@ pub mod test {
             status: super::DTCStatus::None,
             mil_on: false,
             readiness_flag: false,
+            raw_status: 0,
         };
         println!("{:04X}", iso15031_6_dtc.raw);
         println!("{}", iso15031_6_dtc.get_name_as_string());
     }
+
+    #[test]
+    fn test_uds_dtc_status() {
+        let mask = super::DtcStatusMask::PENDING_DTC | super::DtcStatusMask::CONFIRMED_DTC;
+        assert_eq!(u8::from(mask), 0x0C);
+        assert!(mask.intersects(super::DtcStatusMask::CONFIRMED_DTC));
+        assert!(!mask.contains(super::DtcStatusMask(0x0D)));
+
+        let dtc = DTC::from_uds(super::DTCFormatType::Iso14229_1, 0x123456, 0x89);
+        assert_eq!(dtc.status, super::DTCStatus::Active);
+        assert!(dtc.mil_on);
+        assert!(dtc.readiness_flag);
+        assert!(dtc.get_uds_status().is_test_failed());
+        assert!(!dtc.get_uds_status().is_pending_dtc());
+    }
 }
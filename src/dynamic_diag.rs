@@ -8,7 +8,7 @@ use std::{
 
 use crate::{
     channel::IsoTPSettings,
-    dtc::DTC,
+    dtc::{DtcStatusMask, DTC},
     hardware::Hardware,
     kwp2000::{self, Kwp2000DiagnosticServer, Kwp2000ServerOptions, Kwp2000VoidHandler},
     uds::{UDSSessionType, UdsDiagnosticServer, UdsServerOptions, UdsVoidHandler},
@@ -193,7 +193,7 @@ impl DynamicDiagSession {
     pub fn read_all_dtcs(&mut self) -> DiagServerResult<Vec<DTC>> {
         match self.session.borrow_mut() {
             DynamicSessionType::Kwp(k) => k.read_stored_dtcs(kwp2000::DTCRange::All),
-            DynamicSessionType::Uds(u) => u.get_dtcs_by_status_mask(0xFF),
+            DynamicSessionType::Uds(u) => u.get_dtcs_by_status_mask(DtcStatusMask::all()),
         }
     }
 
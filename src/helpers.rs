@@ -5,7 +5,8 @@ use log::{debug, error, warn};
 use std::time::{Duration, Instant};
 
 use crate::{
-    channel::PayloadChannel, BaseServerPayload, BaseServerSettings, DiagError, DiagServerResult,
+    channel::{ChannelError, PayloadChannel},
+    BaseServerPayload, BaseServerSettings, DiagError, DiagServerResult,
 };
 
 /// Checks if the response payload matches the request ServiceID.
@@ -41,43 +42,140 @@ pub(crate) fn perform_cmd<
     busy_repeat_byte: u8,
     lookup_func: L,
 ) -> DiagServerResult<Vec<u8>> {
+    perform_cmd_with_unsolicited(
+        addr,
+        cmd,
+        settings,
+        channel,
+        busy_repeat_byte,
+        lookup_func,
+        None,
+    )
+}
+
+/// Handler for messages which the ECU sends without a matching request.
+/// Returns true if the message was consumed by the handler
+pub(crate) type UnsolicitedHandler<'a> = &'a mut dyn FnMut(&[u8]) -> bool;
+
+/// Same as [perform_cmd], but any message received from the ECU which `on_unsolicited` accepts
+/// (Returns true for) is passed to it, rather than being treated as the response to `cmd`.
+/// Messages which were received prior to sending `cmd` are also passed to `on_unsolicited`,
+/// rather than being cleared from the channel's Rx buffer.
+///
+/// This is used for messages which the ECU sends without a matching request
+/// (For example periodic data responses)
+pub(crate) fn perform_cmd_with_unsolicited<
+    P: BaseServerPayload,
+    T: BaseServerSettings,
+    C: PayloadChannel,
+    L: FnOnce(u8) -> String,
+>(
+    addr: u32,
+    cmd: &P,
+    settings: &T,
+    channel: &mut C,
+    busy_repeat_byte: u8,
+    lookup_func: L,
+    mut on_unsolicited: Option<UnsolicitedHandler<'_>>,
+) -> DiagServerResult<Vec<u8>> {
+    if let Some(handler) = on_unsolicited.as_deref_mut() {
+        // Hand over anything pending before the buffer is cleared
+        while let Ok(msg) = channel.read_bytes(0) {
+            if msg.is_empty() {
+                break;
+            }
+            handler(&msg);
+        }
+    }
     // Clear IO buffers
     channel.clear_tx_buffer()?;
     channel.clear_rx_buffer()?;
     let target = cmd.get_sid_byte();
-    if !cmd.requires_response() {
+    let suppressed = cmd.suppress_positive_response();
+    if !cmd.requires_response() && !suppressed {
         // Just send the data and return an empty response
         debug!("Request doesn't require response. Just sending.");
         channel.write_bytes(addr, cmd.to_bytes(), settings.get_write_timeout_ms())?;
         return Ok(Vec::new());
     }
-    let res = channel.read_write_bytes(
-        addr,
-        cmd.to_bytes(),
-        settings.get_write_timeout_ms(),
-        settings.get_read_timeout_ms(),
-    )?;
+    let read_timeout_ms = if suppressed {
+        settings.get_suppressed_response_timeout_ms()
+    } else {
+        settings.get_read_timeout_ms()
+    };
+    let mut res = if suppressed {
+        // ECU only responds if something went wrong, so no response within P2 is a success
+        debug!("Positive response suppressed. Listening for a negative response");
+        channel.write_bytes(addr, cmd.to_bytes(), settings.get_write_timeout_ms())?;
+        match channel.read_bytes(read_timeout_ms) {
+            Ok(res) => res,
+            Err(ChannelError::ReadTimeout | ChannelError::BufferEmpty) => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        }
+    } else {
+        channel.read_write_bytes(
+            addr,
+            cmd.to_bytes(),
+            settings.get_write_timeout_ms(),
+            read_timeout_ms,
+        )?
+    };
+    if let Some(handler) = on_unsolicited.as_deref_mut() {
+        while handler(&res) {
+            res = match channel.read_bytes(read_timeout_ms) {
+                Ok(res) => res,
+                Err(ChannelError::ReadTimeout | ChannelError::BufferEmpty) if suppressed => {
+                    return Ok(Vec::new())
+                }
+                Err(e) => return Err(e.into()),
+            };
+        }
+    }
     if res.is_empty() {
+        if suppressed {
+            return Ok(Vec::new());
+        }
         return Err(DiagError::EmptyResponse);
     }
     if res[0] == 0x7F {
         if res[2] == busy_repeat_byte {
             warn!("ECU Responded with busy_repeat_request! Retrying in 500ms");
             std::thread::sleep(std::time::Duration::from_millis(500));
-            return perform_cmd(addr, cmd, settings, channel, busy_repeat_byte, lookup_func);
+            return perform_cmd_with_unsolicited(
+                addr,
+                cmd,
+                settings,
+                channel,
+                busy_repeat_byte,
+                lookup_func,
+                on_unsolicited,
+            );
         }
         if res[2] == 0x78 {
             // Always busy wait for response
             warn!("ECU Responded with await_response! Waiting for real response");
             // For both UDS or
             // Wait a bit longer for the ECU response
-            let timestamp = Instant::now();
-            while timestamp.elapsed() <= Duration::from_millis(2000) {
+            let mut timestamp = Instant::now();
+            let pending_timeout =
+                Duration::from_millis(settings.get_response_pending_timeout_ms() as u64);
+            while timestamp.elapsed() <= pending_timeout {
                 if let Ok(res2) = channel.read_bytes(settings.get_read_timeout_ms()) {
+                    if let Some(handler) = on_unsolicited.as_deref_mut() {
+                        if handler(&res2) {
+                            continue;
+                        }
+                    }
                     if res2.is_empty() {
                         error!("ECU Response was empty after await_response!?");
                         return Err(DiagError::EmptyResponse);
                     }
+                    if res2.len() > 2 && res2[0] == 0x7F && res2[2] == 0x78 {
+                        // ECU needs even more time. Restart the wait
+                        debug!("ECU Responded with await_response again. Waiting longer");
+                        timestamp = Instant::now();
+                        continue;
+                    }
                     return if res2[0] == 0x7F {
                         // Still an error. Give up
                         error!("ECU Still responded negatively (0x{:02X?}) after await_response. Giving up.", res2[2]);
@@ -102,3 +200,112 @@ pub(crate) fn perform_cmd<
     }
     check_pos_response_id(target, res) // ECU Response OK!
 }
+
+#[cfg(test)]
+mod helpers_test {
+    use std::collections::VecDeque;
+
+    use super::perform_cmd;
+    use crate::{
+        channel::{ChannelError, ChannelResult, PayloadChannel},
+        BaseServerPayload, BaseServerSettings, DiagError,
+    };
+
+    struct TestChannel(VecDeque<Vec<u8>>);
+
+    impl PayloadChannel for TestChannel {
+        fn open(&mut self) -> ChannelResult<()> {
+            Ok(())
+        }
+
+        fn close(&mut self) -> ChannelResult<()> {
+            Ok(())
+        }
+
+        fn set_ids(&mut self, _send: u32, _recv: u32) -> ChannelResult<()> {
+            Ok(())
+        }
+
+        fn read_bytes(&mut self, _timeout_ms: u32) -> ChannelResult<Vec<u8>> {
+            self.0.pop_front().ok_or(ChannelError::ReadTimeout)
+        }
+
+        fn write_bytes(
+            &mut self,
+            _addr: u32,
+            _buffer: &[u8],
+            _timeout_ms: u32,
+        ) -> ChannelResult<()> {
+            Ok(())
+        }
+
+        fn clear_rx_buffer(&mut self) -> ChannelResult<()> {
+            Ok(())
+        }
+
+        fn clear_tx_buffer(&mut self) -> ChannelResult<()> {
+            Ok(())
+        }
+    }
+
+    struct TestSettings;
+
+    impl BaseServerSettings for TestSettings {
+        fn get_write_timeout_ms(&self) -> u32 {
+            0
+        }
+
+        fn get_read_timeout_ms(&self) -> u32 {
+            0
+        }
+    }
+
+    struct SuppressedCmd(Vec<u8>);
+
+    impl BaseServerPayload for SuppressedCmd {
+        fn get_payload(&self) -> &[u8] {
+            &self.0[1..]
+        }
+
+        fn get_sid_byte(&self) -> u8 {
+            self.0[0]
+        }
+
+        fn to_bytes(&self) -> &[u8] {
+            &self.0
+        }
+
+        fn requires_response(&self) -> bool {
+            false
+        }
+
+        fn suppress_positive_response(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_suppressed_positive_response() {
+        let cmd = SuppressedCmd(vec![0x31, 0x81, 0x02, 0x00]);
+        // No response within P2
+        let mut channel = TestChannel(VecDeque::new());
+        let res = perform_cmd(0, &cmd, &TestSettings, &mut channel, 0x21, |_| {
+            String::new()
+        });
+        assert_eq!(res.unwrap(), Vec::<u8>::new());
+
+        // Negative response
+        let mut channel = TestChannel(vec![vec![0x7F, 0x31, 0x22]].into());
+        let res = perform_cmd(0, &cmd, &TestSettings, &mut channel, 0x21, |_| {
+            String::new()
+        });
+        assert!(matches!(res, Err(DiagError::ECUError { code: 0x22, .. })));
+
+        // Response pending, then the ECU must send its final response
+        let mut channel = TestChannel(vec![vec![0x7F, 0x31, 0x78], vec![0x71, 0x01]].into());
+        let res = perform_cmd(0, &cmd, &TestSettings, &mut channel, 0x21, |_| {
+            String::new()
+        });
+        assert_eq!(res.unwrap(), vec![0x71, 0x01]);
+    }
+}
@@ -0,0 +1,277 @@
+//! Provides methods for defining local identifiers on the ECU at runtime
+//!
+//! A dynamically defined local identifier combines parts of other local identifiers, common identifiers
+//! and/or memory regions into a single local identifier, which can then be read with
+//! [Kwp2000DiagnosticServer::read_custom_local_identifier]. This allows many values to be read with a
+//! single request.
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{read_memory_by_address::MAX_MEMORY_ADDRESS, KWP2000Command, Kwp2000DiagnosticServer};
+
+// DynamicallyDefineLocalIdentifier definition modes
+const DEFINE_BY_LOCAL_IDENTIFIER: u8 = 0x01;
+const DEFINE_BY_COMMON_IDENTIFIER: u8 = 0x02;
+const DEFINE_BY_MEMORY_ADDRESS: u8 = 0x03;
+const CLEAR_DYNAMICALLY_DEFINED_LOCAL_IDENTIFIER: u8 = 0x04;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Source of data for a dynamically defined local identifier
+pub enum DynamicLidSource {
+    /// Part of the record of another local identifier
+    LocalIdentifier {
+        /// Source local identifier
+        lid: u8,
+        /// Position of the first byte to use in the source record.
+        /// NOTE: The first byte of the record is position 1
+        position: u8,
+        /// Number of bytes to use from the source record
+        size: u8,
+    },
+    /// Part of the record of a common identifier
+    CommonIdentifier {
+        /// Source common identifier
+        id: u16,
+        /// Position of the first byte to use in the source record.
+        /// NOTE: The first byte of the record is position 1
+        position: u8,
+        /// Number of bytes to use from the source record
+        size: u8,
+    },
+    /// A memory region on the ECU
+    MemoryAddress {
+        /// Address of the memory region (Max 0xFFFFFF)
+        address: u32,
+        /// Size of the memory region
+        size: u8,
+    },
+}
+
+impl DynamicLidSource {
+    fn data_len(&self) -> u8 {
+        match self {
+            DynamicLidSource::LocalIdentifier { size, .. } => *size,
+            DynamicLidSource::CommonIdentifier { size, .. } => *size,
+            DynamicLidSource::MemoryAddress { size, .. } => *size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Builder for a dynamically defined local identifier. The record of the local identifier is made up
+/// of each source in the order they were added.
+///
+/// Once built, the local identifier is defined on the ECU using
+/// [Kwp2000DiagnosticServer::define_dynamic_local_identifier]
+pub struct DynamicLidBuilder {
+    lid: u8,
+    sources: Vec<DynamicLidSource>,
+}
+
+impl DynamicLidBuilder {
+    /// Creates a new builder for a dynamically defined local identifier
+    ///
+    /// ## Parameters
+    /// * lid - The local identifier to define (0xF0-0xF9)
+    pub fn new(lid: u8) -> Self {
+        Self {
+            lid,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Adds part of the record of another local identifier
+    ///
+    /// ## Parameters
+    /// * lid - Source local identifier
+    /// * position - Position of the first byte to use in the source record (1 based)
+    /// * size - Number of bytes to use from the source record
+    pub fn add_local_identifier(mut self, lid: u8, position: u8, size: u8) -> Self {
+        self.sources.push(DynamicLidSource::LocalIdentifier {
+            lid,
+            position,
+            size,
+        });
+        self
+    }
+
+    /// Adds part of the record of a common identifier
+    ///
+    /// ## Parameters
+    /// * id - Source common identifier
+    /// * position - Position of the first byte to use in the source record (1 based)
+    /// * size - Number of bytes to use from the source record
+    pub fn add_common_identifier(mut self, id: u16, position: u8, size: u8) -> Self {
+        self.sources
+            .push(DynamicLidSource::CommonIdentifier { id, position, size });
+        self
+    }
+
+    /// Adds a memory region
+    ///
+    /// ## Parameters
+    /// * address - Address of the memory region (Max 0xFFFFFF)
+    /// * size - Size of the memory region
+    pub fn add_memory_address(mut self, address: u32, size: u8) -> Self {
+        self.sources
+            .push(DynamicLidSource::MemoryAddress { address, size });
+        self
+    }
+
+    /// Returns the local identifier being defined
+    pub fn get_lid(&self) -> u8 {
+        self.lid
+    }
+
+    /// Returns the sources of the local identifier
+    pub fn get_sources(&self) -> &[DynamicLidSource] {
+        &self.sources
+    }
+
+    /// Returns the total length of the record of the local identifier
+    pub fn data_len(&self) -> usize {
+        self.sources.iter().map(|x| x.data_len() as usize).sum()
+    }
+
+    /// Builds the arguments for each DynamicallyDefineLocalIdentifier request required to define the
+    /// local identifier. Each source is defined with its own request, at the position in the record
+    /// following the previous source
+    pub(crate) fn build_requests(&self) -> DiagServerResult<Vec<Vec<u8>>> {
+        if !(0xF0..=0xF9).contains(&self.lid) || self.sources.is_empty() || self.data_len() > 0xFF {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let mut requests = Vec::with_capacity(self.sources.len());
+        // Position in the dynamically defined record (1 based)
+        let mut record_position = 1u8;
+        for source in &self.sources {
+            let mut args = vec![self.lid];
+            match source {
+                DynamicLidSource::LocalIdentifier {
+                    lid,
+                    position,
+                    size,
+                } => {
+                    if *position == 0 {
+                        return Err(DiagError::ParameterInvalid);
+                    }
+                    args.extend_from_slice(&[
+                        DEFINE_BY_LOCAL_IDENTIFIER,
+                        record_position,
+                        *size,
+                        *lid,
+                        *position,
+                    ]);
+                }
+                DynamicLidSource::CommonIdentifier { id, position, size } => {
+                    if *position == 0 {
+                        return Err(DiagError::ParameterInvalid);
+                    }
+                    args.extend_from_slice(&[
+                        DEFINE_BY_COMMON_IDENTIFIER,
+                        record_position,
+                        *size,
+                        (id >> 8) as u8,
+                        *id as u8,
+                        *position,
+                    ]);
+                }
+                DynamicLidSource::MemoryAddress { address, size } => {
+                    if *address > MAX_MEMORY_ADDRESS {
+                        return Err(DiagError::ParameterInvalid);
+                    }
+                    args.extend_from_slice(&[
+                        DEFINE_BY_MEMORY_ADDRESS,
+                        record_position,
+                        *size,
+                        (address >> 16) as u8,
+                        (address >> 8) as u8,
+                        *address as u8,
+                    ]);
+                }
+            }
+            requests.push(args);
+            record_position = record_position.wrapping_add(source.data_len());
+        }
+        Ok(requests)
+    }
+}
+
+impl Kwp2000DiagnosticServer {
+    /// Defines a dynamically defined local identifier on the ECU. If the local identifier is already
+    /// defined, the ECU may append to or overwrite parts of the existing definition, so use
+    /// [Kwp2000DiagnosticServer::clear_dynamic_local_identifier] first to redefine a local identifier.
+    ///
+    /// Once defined, the record is read using [Kwp2000DiagnosticServer::read_custom_local_identifier]
+    ///
+    /// ## Parameters
+    /// * builder - The definition of the local identifier
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if the local identifier is not within 0xF0-0xF9,
+    /// no sources were added, or the record would be longer than 255 bytes
+    pub fn define_dynamic_local_identifier(
+        &mut self,
+        builder: &DynamicLidBuilder,
+    ) -> DiagServerResult<()> {
+        for args in builder.build_requests()? {
+            self.execute_dynamic_lid_request(&args)?;
+        }
+        Ok(())
+    }
+
+    /// Clears a dynamically defined local identifier on the ECU
+    ///
+    /// ## Parameters
+    /// * lid - The local identifier to clear (0xF0-0xF9)
+    pub fn clear_dynamic_local_identifier(&mut self, lid: u8) -> DiagServerResult<()> {
+        if !(0xF0..=0xF9).contains(&lid) {
+            return Err(DiagError::ParameterInvalid);
+        }
+        self.execute_dynamic_lid_request(&[lid, CLEAR_DYNAMICALLY_DEFINED_LOCAL_IDENTIFIER])
+    }
+
+    fn execute_dynamic_lid_request(&mut self, args: &[u8]) -> DiagServerResult<()> {
+        let res = self.execute_command_with_response(
+            KWP2000Command::DynamicallyDefineLocalIdentifier,
+            args,
+        )?;
+        if res.len() < 2 {
+            // Require Positive SID, IDENT
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if res[1] != args[0] {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected local identifier 0x{:02X}, got local identifier 0x{:02X}",
+                args[0], res[1]
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod dynamically_define_local_identifier_test {
+    use super::DynamicLidBuilder;
+
+    #[test]
+    fn test_build_requests() {
+        let builder = DynamicLidBuilder::new(0xF0)
+            .add_local_identifier(0x01, 3, 2)
+            .add_common_identifier(0x1234, 1, 1)
+            .add_memory_address(0x012345, 4);
+        assert_eq!(builder.data_len(), 7);
+        assert_eq!(
+            builder.build_requests().unwrap(),
+            vec![
+                vec![0xF0, 0x01, 0x01, 0x02, 0x01, 0x03],
+                vec![0xF0, 0x02, 0x03, 0x01, 0x12, 0x34, 0x01],
+                vec![0xF0, 0x03, 0x04, 0x04, 0x01, 0x23, 0x45],
+            ]
+        );
+        assert!(DynamicLidBuilder::new(0xF0).build_requests().is_err());
+        assert!(DynamicLidBuilder::new(0x01)
+            .add_memory_address(0x012345, 4)
+            .build_requests()
+            .is_err());
+    }
+}
@@ -27,6 +27,7 @@ use crate::{
 };
 
 mod clear_diagnostic_information;
+mod dynamically_define_local_identifier;
 mod ecu_reset;
 mod ioctl_mgr;
 mod message_transmission;
@@ -36,11 +37,20 @@ mod read_dtc_by_status;
 mod read_ecu_identification;
 mod read_memory_by_address;
 mod read_status_of_dtc;
+mod request_download;
+mod request_transfer_exit;
+mod request_upload;
+mod respond_on_event;
 mod routine;
 mod security_access;
 mod start_diagnostic_session;
+mod transfer_data;
+mod write_data_by_identifier;
+mod write_data_by_local_id;
+mod write_memory_by_address;
 
 pub use clear_diagnostic_information::*;
+pub use dynamically_define_local_identifier::*;
 pub use ecu_reset::*;
 pub use ioctl_mgr::*;
 pub use message_transmission::*;
@@ -48,8 +58,9 @@ pub use read_data_by_identifier::*;
 pub use read_data_by_local_id::*;
 pub use read_dtc_by_status::*;
 pub use read_ecu_identification::*;
-pub use read_memory_by_address::*;
 pub use read_status_of_dtc::*;
+pub use request_download::*;
+pub use respond_on_event::*;
 pub use routine::*;
 pub use security_access::*;
 pub use start_diagnostic_session::*;
@@ -85,9 +96,9 @@ pub enum KWP2000Command {
     DisableNormalMessageTransmission,
     /// Enables normal CAN message transmission from an ECU.
     EnableNormalMessageTransmission,
-    ///
+    /// Defines a local identifier on the ECU at runtime.
     DynamicallyDefineLocalIdentifier,
-    ///
+    /// Writes data to the ECU using a unique identifier.
     WriteDataByIdentifier,
     ///
     InputOutputControlByLocalIdentifier,
@@ -97,17 +108,17 @@ pub enum KWP2000Command {
     StopRoutineByLocalIdentifier,
     /// requests results of an executed routine given a local identifier.
     RequestRoutineResultsByLocalIdentifier,
-    ///
+    /// Requests a data download (Tester to ECU).
     RequestDownload,
-    ///
+    /// Requests a data upload (ECU to Tester).
     RequestUpload,
-    ///
+    /// Transfers a block of data to or from the ECU.
     TransferData,
-    ///
+    /// Terminates a data transfer.
     RequestTransferExit,
-    ///
+    /// Writes data to the ECU using a local identifier.
     WriteDataByLocalIdentifier,
-    ///
+    /// Writes memory to the ECU by address.
     WriteMemoryByAddress,
     /// Tester present message. [Kwp2000DiagnosticServer] will automatically send this,
     /// so no need to manually create a message with this SID
@@ -247,6 +258,30 @@ fn lookup_kwp_nrc(x: u8) -> String {
     format!("{:?}", KWP2000Error::from(x))
 }
 
+/// Performs a command on the diagnostic server thread. If the ECU is responding to events,
+/// any event responses received whilst performing the command are passed to `event_state`
+fn perform_kwp_cmd<C: IsoTPChannel>(
+    addr: u32,
+    cmd: &Kwp2000Cmd,
+    settings: &Kwp2000ServerOptions,
+    channel: &mut C,
+    event_state: &mut EventState,
+) -> DiagServerResult<Vec<u8>> {
+    if !event_state.is_active() {
+        return helpers::perform_cmd(addr, cmd, settings, channel, 0x21, lookup_kwp_nrc);
+    }
+    let sid = cmd.get_sid_byte();
+    helpers::perform_cmd_with_unsolicited(
+        addr,
+        cmd,
+        settings,
+        channel,
+        0x21,
+        lookup_kwp_nrc,
+        Some(&mut |x| event_state.handle_message(Some(sid), x)),
+    )
+}
+
 impl From<u8> for KWP2000Error {
     fn from(p: u8) -> Self {
         match p {
@@ -336,6 +371,11 @@ impl BaseServerPayload for Kwp2000Cmd {
     fn requires_response(&self) -> bool {
         self.response_required
     }
+
+    fn suppress_positive_response(&self) -> bool {
+        // KWP2000 only lets the tester suppress the positive response of tester present (responseRequired = No)
+        self.get_kwp_sid() == KWP2000Command::TesterPresent && self.bytes.get(1) == Some(&0x02)
+    }
 }
 
 /// Base handler for KWP2000
@@ -365,6 +405,8 @@ pub struct Kwp2000ServerOptions {
     /// Tester present minimum send interval in ms
     pub tester_present_interval_ms: u32,
     /// Configures if the diagnostic server will poll for a response from tester present.
+    /// If false, tester present is sent with responseRequired set to No, and the server only
+    /// listens for a negative response
     pub tester_present_require_response: bool,
 }
 
@@ -387,6 +429,7 @@ pub struct Kwp2000DiagnosticServer {
     rx: mpsc::Receiver<DiagServerResult<Vec<u8>>>,
     repeat_count: u32,
     repeat_interval: std::time::Duration,
+    roe_handler: EventHandlerSlot,
 }
 
 impl Kwp2000DiagnosticServer {
@@ -420,10 +463,13 @@ impl Kwp2000DiagnosticServer {
 
         let (tx_cmd, rx_cmd) = mpsc::channel::<Kwp2000Cmd>();
         let (tx_res, rx_res) = mpsc::channel::<DiagServerResult<Vec<u8>>>();
+        let roe_handler = EventHandlerSlot::default();
+        let roe_handler_t = roe_handler.clone();
 
         std::thread::spawn(move || {
             let mut send_tester_present = false;
             let mut last_tester_present_time: Instant = Instant::now();
+            let mut event_state = EventState::new(roe_handler_t);
 
             event_handler.on_event(ServerEvent::ServerStart);
             log::debug!("KWP2000 server start");
@@ -442,15 +488,16 @@ impl Kwp2000DiagnosticServer {
                     );
                     if cmd.get_kwp_sid() == KWP2000Command::StartDiagnosticSession {
                         // Session change! Handle this differently
-                        match helpers::perform_cmd(
+                        match perform_kwp_cmd(
                             settings.send_id,
                             &cmd,
                             &settings,
                             &mut server_channel,
-                            0x21,
-                            lookup_kwp_nrc,
+                            &mut event_state,
                         ) {
                             Ok(res) => {
+                                // ECU stops responding to events when changing session
+                                event_state.on_session_change();
                                 // Set server session type
                                 if cmd.bytes[1] == u8::from(SessionType::Passive)
                                     || cmd.bytes[1] == u8::from(SessionType::Normal)
@@ -483,16 +530,16 @@ impl Kwp2000DiagnosticServer {
                         }
                     } else if cmd.get_kwp_sid() == KWP2000Command::ECUReset {
                         // After successful reset we have to go back to default diag mode! (NO Tester present)
-                        match helpers::perform_cmd(
+                        match perform_kwp_cmd(
                             settings.send_id,
                             &cmd,
                             &settings,
                             &mut server_channel,
-                            0x21,
-                            lookup_kwp_nrc,
+                            &mut event_state,
                         ) {
                             Ok(res) => {
                                 send_tester_present = false;
+                                event_state.on_session_change();
                                 // Send response to client
                                 if tx_res.send(Ok(res)).is_err() {
                                     // Terminate! Something has gone wrong and data can no longer be sent to client
@@ -514,14 +561,16 @@ impl Kwp2000DiagnosticServer {
                         }
                     } else {
                         // Generic command just perform it
-                        let res = helpers::perform_cmd(
+                        let res = perform_kwp_cmd(
                             settings.send_id,
                             &cmd,
                             &settings,
                             &mut server_channel,
-                            0x21,
-                            lookup_kwp_nrc,
+                            &mut event_state,
                         );
+                        if res.is_ok() && cmd.get_kwp_sid() == KWP2000Command::ResponseOnEvent {
+                            event_state.on_request(&cmd);
+                        }
                         event_handler.on_event(ServerEvent::Response(&res));
                         //event_handler.on_event(&res);
                         if tx_res.send(res).is_err() {
@@ -556,19 +605,28 @@ impl Kwp2000DiagnosticServer {
                         x => x,
                     };
 
-                    if let Err(e) = helpers::perform_cmd(
+                    if let Err(e) = perform_kwp_cmd(
                         addr,
                         &cmd,
                         &settings,
                         &mut server_channel,
-                        0x21,
-                        lookup_kwp_nrc,
+                        &mut event_state,
                     ) {
                         event_handler.on_event(ServerEvent::TesterPresentError(e))
                     }
                     last_tester_present_time = Instant::now();
                 }
 
+                // Collect event responses whilst idle
+                if event_state.is_active() {
+                    while let Ok(msg) = server_channel.read_bytes(0) {
+                        if msg.is_empty() {
+                            break;
+                        }
+                        event_state.handle_message(None, &msg);
+                    }
+                }
+
                 std::thread::sleep(std::time::Duration::from_millis(10));
             }
             // Goodbye server
@@ -586,6 +644,7 @@ impl Kwp2000DiagnosticServer {
             settings,
             repeat_count: 3,
             repeat_interval: std::time::Duration::from_millis(1000),
+            roe_handler,
         })
     }
 
@@ -644,7 +703,11 @@ impl DiagnosticServer<KWP2000Command> for Kwp2000DiagnosticServer {
         }
     }
 
-    /// Send a command to the ECU, but don't receive a response
+    /// Send a command to the ECU, but don't receive a response.
+    ///
+    /// If the request tells the ECU not to send a positive response (Tester present with
+    /// responseRequired set to No), then the server still listens for a negative response
+    /// from the ECU, which is returned as an error.
     ///
     /// ## Parameters
     /// * sid - The Service ID of the command
@@ -654,7 +717,9 @@ impl DiagnosticServer<KWP2000Command> for Kwp2000DiagnosticServer {
         self.exec_command(cmd).map(|_| ())
     }
 
-    /// Sends an arbitrary byte array to the ECU, and does not query response from the ECU
+    /// Sends an arbitrary byte array to the ECU, and does not query response from the ECU.
+    /// Like [DiagnosticServer::execute_command], negative responses are still returned
+    /// if the ECU was told not to send a positive response
     fn send_byte_array(&mut self, arr: &[u8]) -> DiagServerResult<()> {
         let cmd = Kwp2000Cmd::from_raw(arr, false);
         self.exec_command(cmd).map(|_| ())
@@ -73,6 +73,7 @@ impl Kwp2000DiagnosticServer {
                 status: DTCStatus::from_kwp_status(status),
                 mil_on: status & 0b10000000 != 0,
                 readiness_flag: status & 0b00010000 != 0,
+                raw_status: status,
             })
         }
         Ok(ret)
@@ -129,6 +130,7 @@ impl Kwp2000DiagnosticServer {
                 status: DTCStatus::from_kwp_status(status),
                 mil_on: status & 0b10000000 != 0,
                 readiness_flag: status & 0b00010000 != 0,
+                raw_status: status,
             })
         }
         Ok(ret)
@@ -164,6 +166,7 @@ impl Kwp2000DiagnosticServer {
                     status: DTCStatus::from_kwp_status(status),
                     mil_on: status & 0b10000000 != 0,
                     readiness_flag: status & 0b00010000 != 0,
+                    raw_status: status,
                 })
             }
             match self.read_extended_supported_dtcs(range) {
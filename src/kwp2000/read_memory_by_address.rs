@@ -1,9 +1,35 @@
 //! Reads contents from the ECU's RAM
 
-use crate::{DiagServerResult, DiagnosticServer};
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
 
 use super::{KWP2000Command, Kwp2000DiagnosticServer};
 
+/// Highest address which can be encoded in the 3 byte address format
+pub(crate) const MAX_MEMORY_ADDRESS: u32 = 0xFFFFFF;
+
+/// Splits a memory region into chunks which can each be accessed with a single request
+///
+/// ## Returns
+/// The address and size of each chunk. [DiagError::ParameterInvalid] is returned if `chunk_size` is 0,
+/// or if the region does not fit within the 3 byte address format
+pub(crate) fn memory_region_chunks(
+    address: u32,
+    size: usize,
+    chunk_size: u8,
+) -> DiagServerResult<Vec<(u32, u8)>> {
+    if chunk_size == 0 || address as u64 + size as u64 > MAX_MEMORY_ADDRESS as u64 + 1 {
+        return Err(DiagError::ParameterInvalid);
+    }
+    let mut chunks = Vec::with_capacity(size / chunk_size as usize + 1);
+    let mut offset = 0;
+    while offset < size {
+        let len = (chunk_size as usize).min(size - offset);
+        chunks.push((address + offset as u32, len as u8));
+        offset += len;
+    }
+    Ok(chunks)
+}
+
 impl Kwp2000DiagnosticServer {
     /// Reads the contents of RAM memory on the ECU given a 3 byte address, and 1 byte size.
     /// The maximum value for address is 0xFFFFFF, any larger values will be clamped.
@@ -21,4 +47,58 @@ impl Kwp2000DiagnosticServer {
             ],
         )
     }
+
+    /// Reads a memory region on the ECU which is larger than a single response allows,
+    /// by splitting it into multiple [Kwp2000DiagnosticServer::read_memory] requests.
+    ///
+    /// NOTE: This function is ONLY indented for ECU development.
+    ///
+    /// ## Parameters
+    /// * address - Start address of the memory region
+    /// * size - Number of bytes to read
+    /// * chunk_size - Maximum number of bytes to read per request
+    /// * on_progress - Called after each request with the number of bytes read so far and the total number of bytes
+    ///
+    /// ## Returns
+    /// The contents of the memory region, without the positive response SID.
+    /// [DiagError::ParameterInvalid] is returned if `chunk_size` is 0, or if the region
+    /// does not fit within the 3 byte address format
+    pub fn read_memory_region<P>(
+        &mut self,
+        address: u32,
+        size: usize,
+        chunk_size: u8,
+        mut on_progress: P,
+    ) -> DiagServerResult<Vec<u8>>
+    where
+        P: FnMut(usize, usize),
+    {
+        let mut res = Vec::with_capacity(size);
+        for (chunk_address, len) in memory_region_chunks(address, size, chunk_size)? {
+            let chunk = self.read_memory(chunk_address, len)?;
+            if chunk.len() != len as usize + 1 {
+                return Err(DiagError::InvalidResponseLength);
+            }
+            res.extend_from_slice(&chunk[1..]);
+            on_progress(res.len(), size);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod read_memory_by_address_test {
+    use super::memory_region_chunks;
+
+    #[test]
+    fn test_memory_region_chunks() {
+        assert_eq!(
+            memory_region_chunks(0x001000, 600, 0xFF).unwrap(),
+            vec![(0x001000, 0xFF), (0x0010FF, 0xFF), (0x0011FE, 0x5A)]
+        );
+        assert!(memory_region_chunks(0x001000, 0, 0xFF).unwrap().is_empty());
+        assert!(memory_region_chunks(0xFFFFF0, 0x10, 0x08).is_ok());
+        assert!(memory_region_chunks(0xFFFFF0, 0x11, 0x08).is_err());
+        assert!(memory_region_chunks(0x001000, 10, 0).is_err());
+    }
 }
@@ -0,0 +1,177 @@
+//! Requests a data download (Tester to ECU). This is used for reprogramming the ECU
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{read_memory_by_address::MAX_MEMORY_ADDRESS, KWP2000Command, Kwp2000DiagnosticServer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+/// Data format identifier of a data transfer. Describes how the transferred data is
+/// compressed and encrypted. The meaning of each method is defined by the vehicle manufacturer.
+///
+/// The default value (Uncompressed and unencrypted) is 0x00
+pub struct DataFormatIdentifier {
+    compression_method: u8,
+    encrypting_method: u8,
+}
+
+impl DataFormatIdentifier {
+    /// Creates a new data format identifier
+    ///
+    /// ## Parameters
+    /// * compression_method - Compression method (0x0-0xF). 0 means uncompressed
+    /// * encrypting_method - Encryption method (0x0-0xF). 0 means unencrypted
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if either method is larger than 0xF
+    pub fn new(compression_method: u8, encrypting_method: u8) -> DiagServerResult<Self> {
+        if compression_method > 0x0F || encrypting_method > 0x0F {
+            return Err(DiagError::ParameterInvalid);
+        }
+        Ok(Self {
+            compression_method,
+            encrypting_method,
+        })
+    }
+
+    /// Returns the compression method
+    pub fn get_compression_method(&self) -> u8 {
+        self.compression_method
+    }
+
+    /// Returns the encrypting method
+    pub fn get_encrypting_method(&self) -> u8 {
+        self.encrypting_method
+    }
+}
+
+impl From<DataFormatIdentifier> for u8 {
+    fn from(x: DataFormatIdentifier) -> Self {
+        x.compression_method << 4 | x.encrypting_method
+    }
+}
+
+impl From<u8> for DataFormatIdentifier {
+    fn from(x: u8) -> Self {
+        Self {
+            compression_method: x >> 4,
+            encrypting_method: x & 0x0F,
+        }
+    }
+}
+
+/// Encodes the arguments of a RequestDownload or RequestUpload request.
+/// This is the 3 byte memory address, data format identifier, then the 3 byte memory size
+///
+/// ## Returns
+/// [DiagError::ParameterInvalid] is returned if `address` or `size` is larger than 0xFFFFFF
+pub(crate) fn encode_transfer_request(
+    dfi: DataFormatIdentifier,
+    address: u32,
+    size: u32,
+) -> DiagServerResult<Vec<u8>> {
+    if address > MAX_MEMORY_ADDRESS || size > 0xFFFFFF {
+        return Err(DiagError::ParameterInvalid);
+    }
+    Ok(vec![
+        (address >> 16) as u8,
+        (address >> 8) as u8,
+        address as u8,
+        dfi.into(),
+        (size >> 16) as u8,
+        (size >> 8) as u8,
+        size as u8,
+    ])
+}
+
+/// Parses maxNumberOfBlockLength from a positive RequestDownload or RequestUpload
+/// response (including the SID). Depending on the ECU, this is either 1 or 2 bytes long
+pub(crate) fn parse_max_number_of_block_length(resp: &[u8]) -> DiagServerResult<u32> {
+    match resp.len() {
+        2 => Ok(resp[1] as u32),
+        3 => Ok((resp[1] as u32) << 8 | resp[2] as u32),
+        _ => Err(DiagError::InvalidResponseLength),
+    }
+}
+
+impl Kwp2000DiagnosticServer {
+    /// Requests the ECU to accept a data download (Tester to ECU) to a memory region.
+    /// After this, data is sent using [Kwp2000DiagnosticServer::transfer_data], and the transfer
+    /// is finished using [Kwp2000DiagnosticServer::request_transfer_exit].
+    ///
+    /// ## Parameters
+    /// * dfi - Data format identifier (Compression and encryption of the data)
+    /// * address - Memory address to download the data to (Max 0xFFFFFF)
+    /// * size - Size of the uncompressed data to download (Max 0xFFFFFF)
+    ///
+    /// ## Returns
+    /// The maxNumberOfBlockLength returned by the ECU. This is the maximum length of
+    /// each TransferData request, including the SID.
+    pub fn request_download(
+        &mut self,
+        dfi: DataFormatIdentifier,
+        address: u32,
+        size: u32,
+    ) -> DiagServerResult<u32> {
+        let args = encode_transfer_request(dfi, address, size)?;
+        let res = self.execute_command_with_response(KWP2000Command::RequestDownload, &args)?;
+        parse_max_number_of_block_length(&res)
+    }
+
+    /// Downloads data to a memory region on the ECU. This performs the full RequestDownload,
+    /// TransferData and RequestTransferExit sequence.
+    ///
+    /// Depending on the ECU, the memory region may need to be erased first using
+    /// [super::RoutineID::FlashErase], and verified afterwards using [super::RoutineID::FlashCheck].
+    ///
+    /// ## Parameters
+    /// * dfi - Data format identifier (Compression and encryption of the data)
+    /// * address - Memory address to download the data to
+    /// * uncompressed_size - Size of the data once decompressed by the ECU. If `dfi` has no compression,
+    ///   this is the length of `data`
+    /// * data - The data to download
+    /// * on_progress - Called after each block with the number of bytes sent so far and the total number of bytes
+    pub fn download_memory<P>(
+        &mut self,
+        dfi: DataFormatIdentifier,
+        address: u32,
+        uncompressed_size: u32,
+        data: &[u8],
+        on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        P: FnMut(usize, usize),
+    {
+        let max_block_len = self.request_download(dfi, address, uncompressed_size)?;
+        self.transfer_data_blocks(max_block_len, data, on_progress)?;
+        self.request_transfer_exit(&[]).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod request_download_test {
+    use super::{encode_transfer_request, parse_max_number_of_block_length, DataFormatIdentifier};
+
+    #[test]
+    fn test_encode_transfer_request() {
+        let dfi = DataFormatIdentifier::new(0x01, 0x00).unwrap();
+        assert_eq!(
+            encode_transfer_request(dfi, 0x012345, 0x000400).unwrap(),
+            vec![0x01, 0x23, 0x45, 0x10, 0x00, 0x04, 0x00]
+        );
+        assert!(encode_transfer_request(dfi, 0x01000000, 0x0400).is_err());
+        assert!(DataFormatIdentifier::new(0x10, 0x00).is_err());
+    }
+
+    #[test]
+    fn test_parse_max_block_length() {
+        assert_eq!(
+            parse_max_number_of_block_length(&[0x74, 0xFE]).unwrap(),
+            0xFE
+        );
+        assert_eq!(
+            parse_max_number_of_block_length(&[0x74, 0x04, 0x02]).unwrap(),
+            0x0402
+        );
+        assert!(parse_max_number_of_block_length(&[0x74]).is_err());
+    }
+}
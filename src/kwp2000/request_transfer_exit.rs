@@ -0,0 +1,21 @@
+//! Terminates a data transfer
+
+use crate::{DiagServerResult, DiagnosticServer};
+
+use super::{KWP2000Command, Kwp2000DiagnosticServer};
+
+impl Kwp2000DiagnosticServer {
+    /// Terminates a data transfer which was started with [Kwp2000DiagnosticServer::request_download]
+    /// or [Kwp2000DiagnosticServer::request_upload]
+    ///
+    /// ## Parameters
+    /// * record - Optional transferRequestParameter (For example, a checksum of the transferred data).
+    ///   The format of this is defined by the vehicle manufacturer
+    ///
+    /// ## Returns
+    /// The transferResponseParameter returned by the ECU, which can be empty
+    pub fn request_transfer_exit(&mut self, record: &[u8]) -> DiagServerResult<Vec<u8>> {
+        self.execute_command_with_response(KWP2000Command::RequestTransferExit, record)
+            .map(|x| x[1..].to_vec())
+    }
+}
@@ -0,0 +1,76 @@
+//! Requests a data upload (ECU to Tester)
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{
+    encode_transfer_request, parse_max_number_of_block_length, DataFormatIdentifier,
+    KWP2000Command, Kwp2000DiagnosticServer,
+};
+
+impl Kwp2000DiagnosticServer {
+    /// Requests the ECU to start a data upload (ECU to Tester) of a memory region.
+    /// After this, data is received using [Kwp2000DiagnosticServer::transfer_data], and the transfer
+    /// is finished using [Kwp2000DiagnosticServer::request_transfer_exit].
+    ///
+    /// ## Parameters
+    /// * dfi - Data format identifier (Compression and encryption of the data)
+    /// * address - Memory address to upload the data from (Max 0xFFFFFF)
+    /// * size - Size of the uncompressed data to upload (Max 0xFFFFFF)
+    ///
+    /// ## Returns
+    /// The maxNumberOfBlockLength returned by the ECU. This is the maximum length of
+    /// each TransferData response, including the SID.
+    pub fn request_upload(
+        &mut self,
+        dfi: DataFormatIdentifier,
+        address: u32,
+        size: u32,
+    ) -> DiagServerResult<u32> {
+        let args = encode_transfer_request(dfi, address, size)?;
+        let res = self.execute_command_with_response(KWP2000Command::RequestUpload, &args)?;
+        parse_max_number_of_block_length(&res)
+    }
+
+    /// Uploads a memory region from the ECU. This performs the full RequestUpload,
+    /// TransferData and RequestTransferExit sequence.
+    ///
+    /// ## Parameters
+    /// * dfi - Data format identifier (Encryption of the data). Compressed uploads are not supported,
+    ///   as the number of bytes the ECU sends is not known up front. Use [Kwp2000DiagnosticServer::request_upload]
+    ///   and [Kwp2000DiagnosticServer::transfer_data] directly for those
+    /// * address - Memory address to upload the data from
+    /// * size - Number of bytes to upload
+    /// * on_progress - Called after each block with the number of bytes received so far and the total number of bytes
+    ///
+    /// ## Returns
+    /// The contents of the memory region. If the ECU sends more data than requested,
+    /// or stops sending data before `size` bytes were received, then [DiagError::InvalidResponseLength]
+    /// is returned. [DiagError::ParameterInvalid] is returned if `dfi` specifies a compression method
+    pub fn upload_memory<P>(
+        &mut self,
+        dfi: DataFormatIdentifier,
+        address: u32,
+        size: u32,
+        mut on_progress: P,
+    ) -> DiagServerResult<Vec<u8>>
+    where
+        P: FnMut(usize, usize),
+    {
+        if dfi.get_compression_method() != 0 {
+            return Err(DiagError::ParameterInvalid);
+        }
+        self.request_upload(dfi, address, size)?;
+        let size = size as usize;
+        let mut res = Vec::with_capacity(size);
+        while res.len() < size {
+            let block = self.transfer_data(&[])?;
+            if block.is_empty() || res.len() + block.len() > size {
+                return Err(DiagError::InvalidResponseLength);
+            }
+            res.extend_from_slice(&block);
+            on_progress(res.len(), size);
+        }
+        self.request_transfer_exit(&[])?;
+        Ok(res)
+    }
+}
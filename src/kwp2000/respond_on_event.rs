@@ -0,0 +1,288 @@
+//! Provides methods for telling the ECU to respond to events
+//!
+//! Once an event has been set up and started, the ECU sends the response of the service to respond to
+//! each time the event occurs within the event window, without a matching request from the tester.
+//! These messages are passed to the handler set with
+//! [Kwp2000DiagnosticServer::set_response_on_event_handler], and are never returned as the response
+//! to another request.
+
+use std::sync::{Arc, Mutex};
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{KWP2000Command, Kwp2000Cmd, Kwp2000DiagnosticServer};
+
+// ResponseOnEvent event types which control events rather than define them
+const STOP_RESPONSE_ON_EVENT: u8 = 0x00;
+const REPORT_ACTIVATED_EVENTS: u8 = 0x04;
+const START_RESPONSE_ON_EVENT: u8 = 0x05;
+const CLEAR_RESPONSE_ON_EVENT: u8 = 0x06;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Event which triggers the ECU to send a response
+pub enum ResponseOnEventType {
+    /// The status of a DTC changed. The event parameter is the DTC status mask to test against
+    OnDtcStatusChange,
+    /// A timer elapsed. The event parameter is the timer rate
+    OnTimerInterrupt,
+    /// The value of a local identifier changed. The event parameter is the local identifier
+    OnChangeOfRecordValue,
+    /// The value of a local identifier matched a comparison. The event parameter is the comparison
+    /// definition
+    OnComparisonOfValues,
+    /// Vehicle manufacturer specific event type
+    Custom(u8),
+}
+
+impl From<ResponseOnEventType> for u8 {
+    fn from(x: ResponseOnEventType) -> Self {
+        match x {
+            ResponseOnEventType::OnDtcStatusChange => 0x01,
+            ResponseOnEventType::OnTimerInterrupt => 0x02,
+            ResponseOnEventType::OnChangeOfRecordValue => 0x03,
+            ResponseOnEventType::OnComparisonOfValues => 0x07,
+            ResponseOnEventType::Custom(x) => x,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Positive response of the ECU to a ResponseOnEvent request
+pub struct ResponseOnEventStatus {
+    /// Number of events which have been identified (Or are active, when reporting activated events)
+    pub number_of_identified_events: u8,
+    /// Event window time echoed by the ECU
+    pub event_window_time: u8,
+    /// Remaining data of the response. This is the event type and event parameter when setting up an event,
+    /// or the records of each active event when reporting activated events
+    pub data: Vec<u8>,
+}
+
+/// Handler for responses sent by the ECU when an event occurs.
+///
+/// This is called from the diagnostic server's thread, so it should return quickly.
+pub trait ResponseOnEventHandler: Send {
+    /// Called for each response the ECU sends as a result of an event
+    ///
+    /// ## Parameters
+    /// * response - The full response sent by the ECU. This is the response of the service to respond to
+    fn on_event(&mut self, response: &[u8]);
+}
+
+impl<F: FnMut(&[u8]) + Send> ResponseOnEventHandler for F {
+    fn on_event(&mut self, response: &[u8]) {
+        self(response)
+    }
+}
+
+type BoxedEventHandler = Box<dyn ResponseOnEventHandler>;
+
+/// Event handler set by the tester. This is shared between the server and its thread
+#[derive(Clone, Default)]
+pub(crate) struct EventHandlerSlot(Arc<Mutex<Option<BoxedEventHandler>>>);
+
+impl std::fmt::Debug for EventHandlerSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventHandlerSlot")
+    }
+}
+
+impl EventHandlerSlot {
+    fn set(&self, handler: Option<BoxedEventHandler>) {
+        if let Ok(mut slot) = self.0.lock() {
+            *slot = handler;
+        }
+    }
+}
+
+/// Tracks if responses on events are active on the diagnostic server thread,
+/// and forwards event responses to the tester's handler
+#[derive(Debug)]
+pub(crate) struct EventState {
+    active: bool,
+    handler: EventHandlerSlot,
+}
+
+impl EventState {
+    pub(crate) fn new(handler: EventHandlerSlot) -> Self {
+        Self {
+            active: false,
+            handler,
+        }
+    }
+
+    /// Returns true if the ECU may send responses on events
+    pub(crate) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Called by the diagnostic server thread after a ResponseOnEvent request
+    /// was successfully sent
+    pub(crate) fn on_request(&mut self, cmd: &Kwp2000Cmd) {
+        match cmd.bytes.get(2).copied() {
+            Some(STOP_RESPONSE_ON_EVENT | CLEAR_RESPONSE_ON_EVENT) => self.active = false,
+            Some(REPORT_ACTIVATED_EVENTS) | None => {}
+            Some(_) => self.active = true,
+        }
+    }
+
+    /// Called by the diagnostic server thread when the diagnostic session changes or the ECU
+    /// is reset, as the ECU stops responding to events
+    pub(crate) fn on_session_change(&mut self) {
+        self.active = false
+    }
+
+    /// Checks if a message from the ECU is an event response, and forwards it to the handler if it is.
+    ///
+    /// ## Parameters
+    /// * request_sid - SID of the request currently awaiting a response, if any. Responses to this SID
+    ///   are never treated as event responses
+    /// * msg - Message from the ECU
+    ///
+    /// ## Returns
+    /// True if the message was an event response
+    pub(crate) fn handle_message(&mut self, request_sid: Option<u8>, msg: &[u8]) -> bool {
+        if !self.active || msg.is_empty() {
+            return false;
+        }
+        if let Some(sid) = request_sid {
+            if msg[0] == sid.wrapping_add(0x40) || (msg[0] == 0x7F && msg.get(1) == Some(&sid)) {
+                return false;
+            }
+        }
+        // Consume the message even if no handler is set, so it is never mistaken for a response
+        if let Ok(mut handler) = self.handler.0.lock() {
+            if let Some(h) = handler.as_mut() {
+                h.on_event(msg);
+            }
+        }
+        true
+    }
+}
+
+impl Kwp2000DiagnosticServer {
+    /// Sets the handler which receives responses sent by the ECU when an event occurs.
+    /// This replaces any previously set handler
+    pub fn set_response_on_event_handler<H: ResponseOnEventHandler + 'static>(
+        &mut self,
+        handler: H,
+    ) {
+        self.roe_handler.set(Some(Box::new(handler)))
+    }
+
+    /// Removes the handler set with [Kwp2000DiagnosticServer::set_response_on_event_handler].
+    /// Event responses sent by the ECU are then discarded
+    pub fn clear_response_on_event_handler(&mut self) {
+        self.roe_handler.set(None)
+    }
+
+    /// Sets up an event which the ECU should respond to. Depending on the ECU, the event
+    /// may also need to be started with [Kwp2000DiagnosticServer::start_response_on_event]
+    ///
+    /// ## Parameters
+    /// * event_window_time - Time window in which the ECU responds to the event. The encoding of
+    ///   this is vehicle manufacturer specific
+    /// * event_type - The event to respond to
+    /// * event_parameter - Parameter of the event (See [ResponseOnEventType])
+    /// * service_to_respond_to - Request (SID and its parameters) which the ECU performs each time
+    ///   the event occurs. The response of this is then sent to the event handler
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `service_to_respond_to` is empty
+    pub fn setup_response_on_event(
+        &mut self,
+        event_window_time: u8,
+        event_type: ResponseOnEventType,
+        event_parameter: &[u8],
+        service_to_respond_to: &[u8],
+    ) -> DiagServerResult<ResponseOnEventStatus> {
+        if service_to_respond_to.is_empty() {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let mut args = vec![event_window_time, event_type.into()];
+        args.extend_from_slice(event_parameter);
+        args.extend_from_slice(service_to_respond_to);
+        self.response_on_event(&args)
+    }
+
+    /// Starts responding to the events which were previously set up
+    ///
+    /// ## Parameters
+    /// * event_window_time - Time window in which the ECU responds to the events
+    pub fn start_response_on_event(
+        &mut self,
+        event_window_time: u8,
+    ) -> DiagServerResult<ResponseOnEventStatus> {
+        self.response_on_event(&[event_window_time, START_RESPONSE_ON_EVENT])
+    }
+
+    /// Stops responding to events. The events remain set up, so they can be started again
+    pub fn stop_response_on_event(&mut self) -> DiagServerResult<ResponseOnEventStatus> {
+        self.response_on_event(&[0x00, STOP_RESPONSE_ON_EVENT])
+    }
+
+    /// Stops responding to events, and clears all events which were set up
+    pub fn clear_response_on_event(&mut self) -> DiagServerResult<ResponseOnEventStatus> {
+        self.response_on_event(&[0x00, CLEAR_RESPONSE_ON_EVENT])
+    }
+
+    /// Reports the events which are currently active on the ECU
+    ///
+    /// ## Returns
+    /// The number of active events, along with the raw records of each event in
+    /// [ResponseOnEventStatus::data]
+    pub fn report_activated_events(&mut self) -> DiagServerResult<ResponseOnEventStatus> {
+        self.response_on_event(&[0x00, REPORT_ACTIVATED_EVENTS])
+    }
+
+    fn response_on_event(&mut self, args: &[u8]) -> DiagServerResult<ResponseOnEventStatus> {
+        let res = self.execute_command_with_response(KWP2000Command::ResponseOnEvent, args)?;
+        if res.len() < 3 {
+            // Require Positive SID, number of identified events, event window time
+            return Err(DiagError::InvalidResponseLength);
+        }
+        Ok(ResponseOnEventStatus {
+            number_of_identified_events: res[1],
+            event_window_time: res[2],
+            data: res[3..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod respond_on_event_test {
+    use std::sync::mpsc;
+
+    use super::{EventHandlerSlot, EventState, KWP2000Command, Kwp2000Cmd};
+
+    #[test]
+    fn test_event_message_handling() {
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        let slot = EventHandlerSlot::default();
+        slot.set(Some(Box::new(move |x: &[u8]| tx.send(x.to_vec()).unwrap())));
+        let mut state = EventState::new(slot);
+        // Nothing set up, so nothing should be consumed
+        assert!(!state.handle_message(None, &[0x61, 0x01, 0x02]));
+
+        state.on_request(&Kwp2000Cmd::new(
+            KWP2000Command::ResponseOnEvent,
+            &[0x02, 0x03, 0x01, 0x21, 0x01],
+            true,
+        ));
+        assert!(state.handle_message(None, &[0x61, 0x01, 0xAA]));
+        assert!(state.handle_message(Some(0x1A), &[0x61, 0x01, 0xBB]));
+        // Responses to the current request are not events
+        assert!(!state.handle_message(Some(0x21), &[0x61, 0x01, 0xCC]));
+        assert!(!state.handle_message(Some(0x21), &[0x7F, 0x21, 0x78]));
+        assert_eq!(rx.try_recv().unwrap(), vec![0x61, 0x01, 0xAA]);
+        assert_eq!(rx.try_recv().unwrap(), vec![0x61, 0x01, 0xBB]);
+        assert!(rx.try_recv().is_err());
+
+        state.on_request(&Kwp2000Cmd::new(
+            KWP2000Command::ResponseOnEvent,
+            &[0x00, 0x00],
+            true,
+        ));
+        assert!(!state.is_active());
+    }
+}
@@ -0,0 +1,58 @@
+//! Transfers data blocks to and from the ECU
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{KWP2000Command, Kwp2000DiagnosticServer};
+
+impl Kwp2000DiagnosticServer {
+    /// Transfers a single block of data to or from the ECU
+    ///
+    /// ## Parameters
+    /// * data - The transferRequestParameter. For a download, this is the block of data to send.
+    ///   For an upload, this is usually empty.
+    ///
+    /// ## Returns
+    /// The transferResponseParameter returned by the ECU. For an upload, this is the block of data received
+    pub fn transfer_data(&mut self, data: &[u8]) -> DiagServerResult<Vec<u8>> {
+        self.execute_command_with_response(KWP2000Command::TransferData, data)
+            .map(|x| x[1..].to_vec())
+    }
+
+    /// Downloads a complete data buffer to the ECU using multiple TransferData requests.
+    /// [Kwp2000DiagnosticServer::request_download] must have been called prior to this function.
+    /// This function does NOT call [Kwp2000DiagnosticServer::request_transfer_exit].
+    ///
+    /// If the ECU responds to a block with 'Request correctly received - Response pending'
+    /// (For example whilst writing to flash), the server waits for the ECU's final response
+    /// before sending the next block.
+    ///
+    /// ## Parameters
+    /// * max_block_len - maxNumberOfBlockLength returned by [Kwp2000DiagnosticServer::request_download]
+    /// * data - The data to download
+    /// * on_progress - Called after each block with the number of bytes sent so far and the total number of bytes
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `max_block_len` is too small to contain any data
+    pub fn transfer_data_blocks<P>(
+        &mut self,
+        max_block_len: u32,
+        data: &[u8],
+        mut on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        P: FnMut(usize, usize),
+    {
+        // maxNumberOfBlockLength includes the SID
+        if max_block_len <= 1 {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let block_size = max_block_len as usize - 1;
+        let mut sent = 0;
+        for block in data.chunks(block_size) {
+            self.transfer_data(block)?;
+            sent += block.len();
+            on_progress(sent, data.len());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+//! This service writes blocks of data to the ECU using 16 bit identifiers.
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{KWP2000Command, Kwp2000DiagnosticServer};
+
+impl Kwp2000DiagnosticServer {
+    /// Writes ECU data using a given identifier
+    ///
+    /// ## Parameters
+    /// * identifier - A 16 bit identifier to write data to on the ECU
+    /// * data - The data record to write
+    ///
+    /// ## Returns
+    /// If the ECU's positive response does not echo back the same identifier, then
+    /// [DiagError::MismatchedResponse] is returned
+    pub fn write_data_by_identifier(
+        &mut self,
+        identifier: u16,
+        data: &[u8],
+    ) -> DiagServerResult<()> {
+        let mut args = Vec::with_capacity(data.len() + 2);
+        args.push((identifier >> 8) as u8);
+        args.push(identifier as u8);
+        args.extend_from_slice(data);
+        let res =
+            self.execute_command_with_response(KWP2000Command::WriteDataByIdentifier, &args)?;
+        if res.len() < 3 {
+            // Require Positive SID, IDENT << 8, IDENT & 0xFF
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let ident_response = ((res[1] as u16) << 8) | (res[2] as u16);
+        if ident_response != identifier {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected identifier 0x{:04X}, got identifier 0x{:04X}",
+                identifier, ident_response
+            )));
+        }
+        Ok(())
+    }
+
+    /// Writes ECU data using a given identifier, then reads the identifier back using
+    /// [Kwp2000DiagnosticServer::read_data_by_identifier] to verify the ECU stored the data.
+    ///
+    /// NOTE: Some ECUs only apply written values after a reset, in which case the read back data will not match.
+    ///
+    /// ## Parameters
+    /// * identifier - A 16 bit identifier to write data to on the ECU
+    /// * data - The data record to write
+    ///
+    /// ## Returns
+    /// If the read back data does not match the written data, then [DiagError::MismatchedResponse] is returned
+    pub fn write_data_by_identifier_verified(
+        &mut self,
+        identifier: u16,
+        data: &[u8],
+    ) -> DiagServerResult<()> {
+        self.write_data_by_identifier(identifier, data)?;
+        let read_back = self.read_data_by_identifier(identifier)?;
+        if read_back != data {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Identifier 0x{:04X} read back as {:02X?}, expected {:02X?}",
+                identifier, read_back, data
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+//! Write data by Local identifier
+//!
+//! This is used for writing coding data to the ECU, such as the Daimler
+//! Software Calibration Number (SCN) coding.
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{KWP2000Command, Kwp2000DiagnosticServer};
+
+impl Kwp2000DiagnosticServer {
+    /// Writes data to the ECU using a local identifier
+    ///
+    /// ## Parameters
+    /// * local_identifier - Local identifier to write to. Valid ranges are the same as for
+    ///   [Kwp2000DiagnosticServer::read_custom_local_identifier]
+    /// * data - The data record to write
+    ///
+    /// ## Returns
+    /// If the ECU's positive response does not echo back the same local identifier, then
+    /// [DiagError::MismatchedResponse] is returned
+    pub fn write_custom_local_identifier(
+        &mut self,
+        local_identifier: u8,
+        data: &[u8],
+    ) -> DiagServerResult<()> {
+        let mut args = Vec::with_capacity(data.len() + 1);
+        args.push(local_identifier);
+        args.extend_from_slice(data);
+        let res =
+            self.execute_command_with_response(KWP2000Command::WriteDataByLocalIdentifier, &args)?;
+        if res.len() < 2 {
+            // Require Positive SID, IDENT
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if res[1] != local_identifier {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected local identifier 0x{:02X}, got local identifier 0x{:02X}",
+                local_identifier, res[1]
+            )));
+        }
+        Ok(())
+    }
+
+    /// Writes data to the ECU using a local identifier, then reads the local identifier back using
+    /// [Kwp2000DiagnosticServer::read_custom_local_identifier] to verify the ECU stored the data.
+    ///
+    /// NOTE: Some ECUs only apply written values after a reset, in which case the read back data will not match.
+    ///
+    /// ## Parameters
+    /// * local_identifier - Local identifier to write to
+    /// * data - The data record to write
+    ///
+    /// ## Returns
+    /// If the read back data does not match the written data, then [DiagError::MismatchedResponse] is returned
+    pub fn write_custom_local_identifier_verified(
+        &mut self,
+        local_identifier: u8,
+        data: &[u8],
+    ) -> DiagServerResult<()> {
+        self.write_custom_local_identifier(local_identifier, data)?;
+        let read_back = self.read_custom_local_identifier(local_identifier)?;
+        if read_back != data {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Local identifier 0x{:02X} read back as {:02X?}, expected {:02X?}",
+                local_identifier, read_back, data
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! Writes contents to the ECU's RAM
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{
+    read_memory_by_address::{memory_region_chunks, MAX_MEMORY_ADDRESS},
+    KWP2000Command, Kwp2000DiagnosticServer,
+};
+
+impl Kwp2000DiagnosticServer {
+    /// Writes data to RAM memory on the ECU given a 3 byte address. At most 255 bytes
+    /// can be written with a single request.
+    ///
+    /// NOTE: This function is ONLY indented for ECU development. In production ECUs,
+    /// use [Kwp2000DiagnosticServer::write_custom_local_identifier] instead
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `data` is empty or longer than 255 bytes,
+    /// or if `address` is larger than 0xFFFFFF. If the ECU's positive response does not echo back
+    /// the same address, then [DiagError::MismatchedResponse] is returned
+    pub fn write_memory(&mut self, address: u32, data: &[u8]) -> DiagServerResult<()> {
+        if data.is_empty() || data.len() > 0xFF || address > MAX_MEMORY_ADDRESS {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let mut args = Vec::with_capacity(data.len() + 4);
+        args.extend_from_slice(&[
+            (address >> 16) as u8,
+            (address >> 8) as u8,
+            address as u8,
+            data.len() as u8,
+        ]);
+        args.extend_from_slice(data);
+        let res =
+            self.execute_command_with_response(KWP2000Command::WriteMemoryByAddress, &args)?;
+        if res.len() < 4 {
+            // Require Positive SID, 3 byte address
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if res[1..4] != args[..3] {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected address {:02X?}, got {:02X?}",
+                &args[..3],
+                &res[1..4]
+            )));
+        }
+        Ok(())
+    }
+
+    /// Writes data to a memory region on the ECU which is larger than a single request allows,
+    /// by splitting it into multiple [Kwp2000DiagnosticServer::write_memory] requests.
+    ///
+    /// NOTE: This function is ONLY indented for ECU development.
+    ///
+    /// ## Parameters
+    /// * address - Start address of the memory region
+    /// * data - Data to write
+    /// * chunk_size - Maximum number of bytes to write per request
+    /// * on_progress - Called after each request with the number of bytes written so far and the total number of bytes
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `chunk_size` is 0, or if the region
+    /// does not fit within the 3 byte address format
+    pub fn write_memory_region<P>(
+        &mut self,
+        address: u32,
+        data: &[u8],
+        chunk_size: u8,
+        mut on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        P: FnMut(usize, usize),
+    {
+        let mut written = 0;
+        for (chunk_address, len) in memory_region_chunks(address, data.len(), chunk_size)? {
+            let end = written + len as usize;
+            self.write_memory(chunk_address, &data[written..end])?;
+            written = end;
+            on_progress(written, data.len());
+        }
+        Ok(())
+    }
+}
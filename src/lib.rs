@@ -107,6 +107,8 @@ pub enum DiagError {
     HardwareError(HardwareError),
     /// ECU Param ID did not match the request, but the Service ID was correct
     MismatchedResponse(String),
+    /// An operation which polls the ECU did not complete within the given time
+    Timeout,
 }
 
 impl std::fmt::Display for DiagError {
@@ -133,6 +135,7 @@ impl std::fmt::Display for DiagError {
             }
             DiagError::HardwareError(e) => write!(f, "Hardware error: {}", e),
             DiagError::MismatchedResponse(e) => write!(f, "Param mismatched response: {}", e),
+            DiagError::Timeout => write!(f, "operation timed out"),
         }
     }
 }
@@ -233,6 +236,16 @@ pub trait BaseServerSettings {
     fn get_write_timeout_ms(&self) -> u32;
     /// Gets the read timeout for reading response messages from the servers channel
     fn get_read_timeout_ms(&self) -> u32;
+    /// Gets the maximum time to wait for the ECU's response after it has responded with
+    /// 'Request correctly received - Response pending' (NRC 0x78)
+    fn get_response_pending_timeout_ms(&self) -> u32 {
+        2000
+    }
+    /// Gets the time to listen for a negative response after sending a request whose positive
+    /// response is suppressed (P2). If the ECU does not respond within this time, the request succeeded
+    fn get_suppressed_response_timeout_ms(&self) -> u32 {
+        50
+    }
 }
 
 /// Basic diagnostic server payload
@@ -245,6 +258,11 @@ pub trait BaseServerPayload {
     fn to_bytes(&self) -> &[u8];
     /// Boolean indicating if the diagnostic server should poll the ECU for a response after sending the payload
     fn requires_response(&self) -> bool;
+    /// Boolean indicating if the ECU has been told not to send a positive response to the payload.
+    /// In this case the diagnostic server still listens for a negative response after sending the payload
+    fn suppress_positive_response(&self) -> bool {
+        false
+    }
 }
 
 /// Converts a single byte into a BCD string
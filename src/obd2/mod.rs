@@ -355,6 +355,7 @@ impl OBD2DiagnosticServer {
                     status: DTCStatus::Pending,
                     mil_on: false,
                     readiness_flag: false,
+                    raw_status: 0,
                 })
             }
         }
@@ -378,6 +379,7 @@ impl OBD2DiagnosticServer {
                         status: DTCStatus::Stored,
                         mil_on: true,
                         readiness_flag: false,
+                        raw_status: 0,
                     })
                 }
             }
@@ -402,6 +404,7 @@ impl OBD2DiagnosticServer {
                         status: DTCStatus::Permanent,
                         mil_on: true,
                         readiness_flag: false,
+                        raw_status: 0,
                     })
                 }
             }
@@ -1 +1,272 @@
+//! Provides methods for reading and modifying the timing parameters of the communication link with the ECU
+//!
+//! The timing parameter record is assumed to contain P2Server_max (2 bytes, 1ms resolution)
+//! followed by P2*Server_max (2 bytes, 10ms resolution), which is the same format the ECU uses
+//! when responding to DiagnosticSessionControl.
 
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use crate::{BaseServerSettings, DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer, UdsServerOptions};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Timing parameter access type
+pub enum TimingParameterAccessType {
+    /// Read the extended timing parameter set supported by the ECU
+    ReadExtendedTimingParameterSet,
+    /// Set the timing parameters back to their default values
+    SetTimingParametersToDefaultValues,
+    /// Read the currently active timing parameters
+    ReadCurrentlyActiveTimingParameters,
+    /// Set the timing parameters to the values provided by the tester
+    SetTimingParametersToGivenValues,
+}
+
+impl From<TimingParameterAccessType> for u8 {
+    fn from(x: TimingParameterAccessType) -> Self {
+        match x {
+            TimingParameterAccessType::ReadExtendedTimingParameterSet => 0x01,
+            TimingParameterAccessType::SetTimingParametersToDefaultValues => 0x02,
+            TimingParameterAccessType::ReadCurrentlyActiveTimingParameters => 0x03,
+            TimingParameterAccessType::SetTimingParametersToGivenValues => 0x04,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Timing parameters of the ECU
+pub struct TimingParameters {
+    /// P2Server_max - Maximum time the ECU takes to start responding to a request, in milliseconds
+    pub p2_ms: u32,
+    /// P2*Server_max - Maximum time the ECU takes to respond after sending a response pending
+    /// message (NRC 0x78), in milliseconds. This has a resolution of 10ms
+    pub p2_star_ms: u32,
+}
+
+impl TimingParameters {
+    /// Decodes a timing parameter record
+    pub(crate) fn from_record(record: &[u8]) -> DiagServerResult<Self> {
+        if record.len() != 4 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        Ok(Self {
+            p2_ms: (record[0] as u32) << 8 | record[1] as u32,
+            p2_star_ms: ((record[2] as u32) << 8 | record[3] as u32) * 10,
+        })
+    }
+
+    /// Encodes the timing parameters into a timing parameter record
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if either parameter is too large to be encoded
+    pub(crate) fn to_record(self) -> DiagServerResult<Vec<u8>> {
+        let p2_star = self.p2_star_ms / 10;
+        if self.p2_ms > 0xFFFF || p2_star > 0xFFFF {
+            return Err(DiagError::ParameterInvalid);
+        }
+        Ok(vec![
+            (self.p2_ms >> 8) as u8,
+            self.p2_ms as u8,
+            (p2_star >> 8) as u8,
+            p2_star as u8,
+        ])
+    }
+}
+
+/// Timing parameters currently in use by the diagnostic server. This is shared between the
+/// server and its thread. A value of 0 means the server's default settings are used
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TimingState {
+    p2_ms: Arc<AtomicU32>,
+    p2_star_ms: Arc<AtomicU32>,
+}
+
+impl TimingState {
+    pub(crate) fn set(&self, params: TimingParameters) {
+        self.p2_ms.store(params.p2_ms, Ordering::Relaxed);
+        self.p2_star_ms.store(params.p2_star_ms, Ordering::Relaxed);
+    }
+
+    pub(crate) fn reset(&self) {
+        self.set(TimingParameters {
+            p2_ms: 0,
+            p2_star_ms: 0,
+        })
+    }
+
+    pub(crate) fn get(&self) -> Option<TimingParameters> {
+        match self.p2_ms.load(Ordering::Relaxed) {
+            0 => None,
+            p2_ms => Some(TimingParameters {
+                p2_ms,
+                p2_star_ms: self.p2_star_ms.load(Ordering::Relaxed),
+            }),
+        }
+    }
+}
+
+/// Server settings with the timing parameters which are currently in use applied
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ActiveTimingSettings<'a> {
+    pub(crate) settings: &'a UdsServerOptions,
+    pub(crate) timing: Option<TimingParameters>,
+}
+
+impl<'a> BaseServerSettings for ActiveTimingSettings<'a> {
+    fn get_write_timeout_ms(&self) -> u32 {
+        self.settings.get_write_timeout_ms()
+    }
+
+    fn get_read_timeout_ms(&self) -> u32 {
+        // P2 is the time the ECU has to start its response, which does not account for
+        // the time the response takes to arrive through the adapter
+        match self.timing {
+            Some(t) => t.p2_ms.max(self.settings.get_read_timeout_ms()),
+            None => self.settings.get_read_timeout_ms(),
+        }
+    }
+
+    fn get_response_pending_timeout_ms(&self) -> u32 {
+        match self.timing {
+            Some(t) if t.p2_star_ms != 0 => t.p2_star_ms,
+            _ => self.settings.get_response_pending_timeout_ms(),
+        }
+    }
+
+    fn get_suppressed_response_timeout_ms(&self) -> u32 {
+        match self.timing {
+            Some(t) => t.p2_ms,
+            None => self.settings.get_suppressed_response_timeout_ms(),
+        }
+    }
+}
+
+impl UdsDiagnosticServer {
+    fn access_timing_parameter(
+        &mut self,
+        access_type: TimingParameterAccessType,
+        record: &[u8],
+    ) -> DiagServerResult<Vec<u8>> {
+        let mut args = Vec::with_capacity(record.len() + 1);
+        args.push(access_type.into());
+        args.extend_from_slice(record);
+        let res = self.execute_command_with_response(UDSCommand::AccessTimingParameters, &args)?;
+        if res.len() < 2 {
+            // Require Positive SID, access type
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if res[1] & 0x7F != u8::from(access_type) {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected timing parameter access type 0x{:02X}, got 0x{:02X}",
+                u8::from(access_type),
+                res[1]
+            )));
+        }
+        Ok(res[2..].to_vec())
+    }
+
+    /// Reads the extended timing parameter set supported by the ECU.
+    /// This does NOT change the timing parameters used by the server.
+    pub fn read_extended_timing_parameter_set(&mut self) -> DiagServerResult<TimingParameters> {
+        let record = self.access_timing_parameter(
+            TimingParameterAccessType::ReadExtendedTimingParameterSet,
+            &[],
+        )?;
+        TimingParameters::from_record(&record)
+    }
+
+    /// Sets the ECU's timing parameters back to their default values. The server
+    /// then uses the timeouts from its [UdsServerOptions] again.
+    pub fn set_timing_parameters_to_default(&mut self) -> DiagServerResult<()> {
+        self.access_timing_parameter(
+            TimingParameterAccessType::SetTimingParametersToDefaultValues,
+            &[],
+        )?;
+        self.timing.reset();
+        Ok(())
+    }
+
+    /// Reads the currently active timing parameters of the ECU. The server then
+    /// uses P2 as its read timeout (Unless its configured read timeout is longer),
+    /// and P2* as its response pending timeout.
+    pub fn read_currently_active_timing_parameters(
+        &mut self,
+    ) -> DiagServerResult<TimingParameters> {
+        let record = self.access_timing_parameter(
+            TimingParameterAccessType::ReadCurrentlyActiveTimingParameters,
+            &[],
+        )?;
+        let params = TimingParameters::from_record(&record)?;
+        self.timing.set(params);
+        Ok(params)
+    }
+
+    /// Sets the ECU's timing parameters. The server then uses P2 as its read timeout
+    /// (Unless its configured read timeout is longer), and P2* as its response pending timeout.
+    ///
+    /// ## Parameters
+    /// * params - The timing parameters to set
+    pub fn set_timing_parameters(&mut self, params: TimingParameters) -> DiagServerResult<()> {
+        self.access_timing_parameter(
+            TimingParameterAccessType::SetTimingParametersToGivenValues,
+            &params.to_record()?,
+        )?;
+        self.timing.set(params);
+        Ok(())
+    }
+
+    /// Returns the timing parameters adopted from the ECU which the server is currently using.
+    /// If [None] is returned, then the server is using the timeouts from its [UdsServerOptions]
+    pub fn get_active_timing_parameters(&self) -> Option<TimingParameters> {
+        self.timing.get()
+    }
+}
+
+#[cfg(test)]
+mod access_timing_parameter_test {
+    use crate::{uds::UdsServerOptions, BaseServerSettings};
+
+    use super::{ActiveTimingSettings, TimingParameters};
+
+    #[test]
+    fn test_timing_parameter_record() {
+        let params = TimingParameters::from_record(&[0x00, 0x32, 0x01, 0xF4]).unwrap();
+        assert_eq!(params.p2_ms, 50);
+        assert_eq!(params.p2_star_ms, 5000);
+        assert_eq!(params.to_record().unwrap(), vec![0x00, 0x32, 0x01, 0xF4]);
+        assert!(TimingParameters::from_record(&[0x00, 0x32]).is_err());
+    }
+
+    #[test]
+    fn test_active_read_timeout() {
+        let settings = UdsServerOptions {
+            send_id: 0x07E1,
+            recv_id: 0x07E9,
+            read_timeout_ms: 1000,
+            write_timeout_ms: 1000,
+            global_tp_id: 0,
+            tester_present_interval_ms: 2000,
+            tester_present_require_response: true,
+        };
+        let mut active = ActiveTimingSettings {
+            settings: &settings,
+            timing: Some(TimingParameters {
+                p2_ms: 50,
+                p2_star_ms: 5000,
+            }),
+        };
+        assert_eq!(active.get_read_timeout_ms(), 1000);
+        assert_eq!(active.get_suppressed_response_timeout_ms(), 50);
+        assert_eq!(active.get_response_pending_timeout_ms(), 5000);
+        active.timing = Some(TimingParameters {
+            p2_ms: 2500,
+            p2_star_ms: 5000,
+        });
+        assert_eq!(active.get_read_timeout_ms(), 2500);
+        assert_eq!(active.get_suppressed_response_timeout_ms(), 2500);
+    }
+}
@@ -0,0 +1,383 @@
+//! Provides methods for certificate based authentication with the ECU (ISO14229-1:2020)
+//!
+//! On newer ECUs, this replaces [UdsDiagnosticServer::request_seed] / [UdsDiagnosticServer::send_key].
+//! The client sends its certificate to the ECU, which responds with a challenge. The client then
+//! proves ownership of the certificate by signing the challenge with its private key.
+//!
+//! Signing is done by an [AuthenticationSigner], so the private key can either be stored locally,
+//! or within a hardware security module.
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Authentication task (Sub function)
+pub enum AuthenticationTask {
+    /// Ends the current authentication, returning the ECU to its unauthenticated state
+    DeAuthenticate,
+    /// Sends the client's certificate to the ECU for verification (Only the client is authenticated)
+    VerifyCertificateUnidirectional,
+    /// Sends the client's certificate to the ECU for verification, and receives the ECU's
+    /// certificate in return (Both the client and the ECU are authenticated)
+    VerifyCertificateBidirectional,
+    /// Sends the proof of ownership of the client's certificate to the ECU
+    ProofOfOwnership,
+    /// Asks the ECU which authentication method it supports
+    AuthenticationConfiguration,
+}
+
+impl From<AuthenticationTask> for u8 {
+    fn from(x: AuthenticationTask) -> Self {
+        match x {
+            AuthenticationTask::DeAuthenticate => 0x00,
+            AuthenticationTask::VerifyCertificateUnidirectional => 0x01,
+            AuthenticationTask::VerifyCertificateBidirectional => 0x02,
+            AuthenticationTask::ProofOfOwnership => 0x03,
+            AuthenticationTask::AuthenticationConfiguration => 0x08,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Authentication return parameter. This is included in every positive response from the ECU
+pub enum AuthenticationReturnParameter {
+    /// The request was accepted
+    RequestAccepted,
+    /// The request was rejected
+    GeneralReject,
+    /// The ECU uses authentication with PKI certificate exchange (APCE)
+    AuthenticationConfigurationAPCE,
+    /// The ECU uses challenge-response authentication (ACR) with asymmetric cryptography
+    AuthenticationConfigurationACRAsymmetric,
+    /// The ECU uses challenge-response authentication (ACR) with symmetric cryptography
+    AuthenticationConfigurationACRSymmetric,
+    /// De-authentication was successful
+    DeAuthenticationSuccessful,
+    /// The certificate was verified, and the ECU now requires proof of ownership
+    CertificateVerifiedOwnershipVerificationNecessary,
+    /// Proof of ownership was verified, authentication is complete
+    OwnershipVerifiedAuthenticationComplete,
+    /// The certificate was verified
+    CertificateVerified,
+    /// Reserved or vehicle manufacturer specific value
+    Other(u8),
+}
+
+impl From<u8> for AuthenticationReturnParameter {
+    fn from(x: u8) -> Self {
+        match x {
+            0x00 => Self::RequestAccepted,
+            0x01 => Self::GeneralReject,
+            0x02 => Self::AuthenticationConfigurationAPCE,
+            0x03 => Self::AuthenticationConfigurationACRAsymmetric,
+            0x04 => Self::AuthenticationConfigurationACRSymmetric,
+            0x10 => Self::DeAuthenticationSuccessful,
+            0x11 => Self::CertificateVerifiedOwnershipVerificationNecessary,
+            0x12 => Self::OwnershipVerifiedAuthenticationComplete,
+            0x13 => Self::CertificateVerified,
+            x => Self::Other(x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Response from the ECU after verifying the client's certificate
+pub struct CertificateVerificationResponse {
+    /// Result of the certificate verification
+    pub return_parameter: AuthenticationReturnParameter,
+    /// Challenge from the ECU which the client must sign to prove ownership of its certificate
+    pub challenge_server: Vec<u8>,
+    /// ECU's certificate (Bidirectional verification only)
+    pub certificate_server: Vec<u8>,
+    /// ECU's proof of ownership of its certificate (Bidirectional verification only)
+    pub proof_of_ownership_server: Vec<u8>,
+    /// ECU's ephemeral public key. This may be empty
+    pub ephemeral_public_key_server: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Response from the ECU after verifying the client's proof of ownership
+pub struct ProofOfOwnershipResponse {
+    /// Result of the proof of ownership verification
+    pub return_parameter: AuthenticationReturnParameter,
+    /// Session key information. This may be empty
+    pub session_key_info: Vec<u8>,
+}
+
+/// Signs challenges from the ECU using the private key belonging to the client's certificate.
+///
+/// This is implemented for any function or closure with the signature
+/// `Fn(&[u8]) -> DiagServerResult<Vec<u8>>`
+pub trait AuthenticationSigner {
+    /// Signs the challenge sent by the ECU, creating the client's proof of ownership
+    ///
+    /// ## Parameters
+    /// * challenge - The challenge sent by the ECU
+    fn sign_challenge(&self, challenge: &[u8]) -> DiagServerResult<Vec<u8>>;
+
+    /// Returns the client's ephemeral public key to send with the proof of ownership,
+    /// for establishing a session key. By default, no key is sent
+    fn get_ephemeral_public_key(&self) -> DiagServerResult<Vec<u8>> {
+        Ok(Vec::new())
+    }
+}
+
+impl<F> AuthenticationSigner for F
+where
+    F: Fn(&[u8]) -> DiagServerResult<Vec<u8>>,
+{
+    fn sign_challenge(&self, challenge: &[u8]) -> DiagServerResult<Vec<u8>> {
+        self(challenge)
+    }
+}
+
+/// Appends a parameter to `args`, prefixed by its 2 byte length
+fn push_length_prefixed(args: &mut Vec<u8>, param: &[u8]) -> DiagServerResult<()> {
+    if param.len() > 0xFFFF {
+        return Err(DiagError::ParameterInvalid);
+    }
+    args.push((param.len() >> 8) as u8);
+    args.push(param.len() as u8);
+    args.extend_from_slice(param);
+    Ok(())
+}
+
+/// Reads a parameter prefixed by its 2 byte length from `res`, starting at `pos`
+fn read_length_prefixed(res: &[u8], pos: &mut usize) -> DiagServerResult<Vec<u8>> {
+    if res.len() < *pos + 2 {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    let len = (res[*pos] as usize) << 8 | res[*pos + 1] as usize;
+    *pos += 2;
+    if res.len() < *pos + len {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    let param = res[*pos..*pos + len].to_vec();
+    *pos += len;
+    Ok(param)
+}
+
+/// Parses the response to a certificate verification request. Parameters after the return
+/// parameter start at position 3 of the response
+fn parse_certificate_verification_response(
+    task: AuthenticationTask,
+    res: &[u8],
+) -> DiagServerResult<CertificateVerificationResponse> {
+    let mut pos = 3;
+    let challenge_server = read_length_prefixed(res, &mut pos)?;
+    let (certificate_server, proof_of_ownership_server) =
+        if task == AuthenticationTask::VerifyCertificateBidirectional {
+            (
+                read_length_prefixed(res, &mut pos)?,
+                read_length_prefixed(res, &mut pos)?,
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+    Ok(CertificateVerificationResponse {
+        return_parameter: res[2].into(),
+        challenge_server,
+        certificate_server,
+        proof_of_ownership_server,
+        ephemeral_public_key_server: read_length_prefixed(res, &mut pos)?,
+    })
+}
+
+impl UdsDiagnosticServer {
+    fn execute_authentication(
+        &mut self,
+        task: AuthenticationTask,
+        args: &[u8],
+    ) -> DiagServerResult<Vec<u8>> {
+        let mut payload = Vec::with_capacity(args.len() + 1);
+        payload.push(task.into());
+        payload.extend_from_slice(args);
+        let res = self.execute_command_with_response(UDSCommand::Authentication, &payload)?;
+        if res.len() < 3 {
+            // Require Positive SID, authentication task, return parameter
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if res[1] & 0x7F != u8::from(task) {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected authentication task 0x{:02X}, got 0x{:02X}",
+                u8::from(task),
+                res[1]
+            )));
+        }
+        Ok(res)
+    }
+
+    /// Ends the current authentication, returning the ECU to its unauthenticated state
+    pub fn de_authenticate(&mut self) -> DiagServerResult<AuthenticationReturnParameter> {
+        self.execute_authentication(AuthenticationTask::DeAuthenticate, &[])
+            .map(|res| res[2].into())
+    }
+
+    /// Asks the ECU which authentication method it supports
+    ///
+    /// ## Returns
+    /// One of the `AuthenticationConfiguration` variants of [AuthenticationReturnParameter]
+    pub fn authentication_configuration(
+        &mut self,
+    ) -> DiagServerResult<AuthenticationReturnParameter> {
+        self.execute_authentication(AuthenticationTask::AuthenticationConfiguration, &[])
+            .map(|res| res[2].into())
+    }
+
+    fn verify_certificate(
+        &mut self,
+        task: AuthenticationTask,
+        communication_configuration: u8,
+        certificate: &[u8],
+        challenge: &[u8],
+    ) -> DiagServerResult<CertificateVerificationResponse> {
+        let mut args = vec![communication_configuration];
+        push_length_prefixed(&mut args, certificate)?;
+        push_length_prefixed(&mut args, challenge)?;
+        let res = self.execute_authentication(task, &args)?;
+        parse_certificate_verification_response(task, &res)
+    }
+
+    /// Sends the client's certificate to the ECU for verification
+    ///
+    /// ## Parameters
+    /// * communication_configuration - Manufacturer specific configuration of secure communication
+    ///   after authentication. Use 0x00 if not in use
+    /// * certificate - The client's certificate
+    /// * challenge - Optional challenge for the ECU. This can be empty
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `certificate` or `challenge` is longer than 0xFFFF bytes
+    pub fn verify_certificate_unidirectional(
+        &mut self,
+        communication_configuration: u8,
+        certificate: &[u8],
+        challenge: &[u8],
+    ) -> DiagServerResult<CertificateVerificationResponse> {
+        self.verify_certificate(
+            AuthenticationTask::VerifyCertificateUnidirectional,
+            communication_configuration,
+            certificate,
+            challenge,
+        )
+    }
+
+    /// Sends the client's certificate to the ECU for verification. The ECU responds with its own
+    /// certificate and proof of ownership, which the client should verify.
+    ///
+    /// ## Parameters
+    /// * communication_configuration - Manufacturer specific configuration of secure communication
+    ///   after authentication. Use 0x00 if not in use
+    /// * certificate - The client's certificate
+    /// * challenge - Challenge for the ECU to sign as its proof of ownership
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `certificate` or `challenge` is longer than 0xFFFF bytes
+    pub fn verify_certificate_bidirectional(
+        &mut self,
+        communication_configuration: u8,
+        certificate: &[u8],
+        challenge: &[u8],
+    ) -> DiagServerResult<CertificateVerificationResponse> {
+        self.verify_certificate(
+            AuthenticationTask::VerifyCertificateBidirectional,
+            communication_configuration,
+            certificate,
+            challenge,
+        )
+    }
+
+    /// Sends the client's proof of ownership of its certificate to the ECU
+    ///
+    /// ## Parameters
+    /// * proof_of_ownership - The ECU's challenge, signed by the client
+    /// * ephemeral_public_key - The client's ephemeral public key. This can be empty
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if either parameter is longer than 0xFFFF bytes
+    pub fn proof_of_ownership(
+        &mut self,
+        proof_of_ownership: &[u8],
+        ephemeral_public_key: &[u8],
+    ) -> DiagServerResult<ProofOfOwnershipResponse> {
+        let mut args = Vec::new();
+        push_length_prefixed(&mut args, proof_of_ownership)?;
+        push_length_prefixed(&mut args, ephemeral_public_key)?;
+        let res = self.execute_authentication(AuthenticationTask::ProofOfOwnership, &args)?;
+        let mut pos = 3;
+        Ok(ProofOfOwnershipResponse {
+            return_parameter: res[2].into(),
+            session_key_info: read_length_prefixed(&res, &mut pos)?,
+        })
+    }
+
+    /// Authenticates with the ECU using unidirectional certificate verification. The client's
+    /// certificate is sent to the ECU, then the ECU's challenge is signed using `signer`
+    /// and sent back as the proof of ownership.
+    ///
+    /// ## Parameters
+    /// * communication_configuration - Manufacturer specific configuration of secure communication
+    ///   after authentication. Use 0x00 if not in use
+    /// * certificate - The client's certificate
+    /// * signer - Signs the ECU's challenge with the private key belonging to `certificate`
+    ///
+    /// ## Returns
+    /// [DiagError::NotSupported] is returned if the ECU verified the certificate, but did not
+    /// ask for proof of ownership
+    pub fn authenticate_unidirectional<S: AuthenticationSigner + ?Sized>(
+        &mut self,
+        communication_configuration: u8,
+        certificate: &[u8],
+        signer: &S,
+    ) -> DiagServerResult<ProofOfOwnershipResponse> {
+        let verification =
+            self.verify_certificate_unidirectional(communication_configuration, certificate, &[])?;
+        if verification.return_parameter
+            != AuthenticationReturnParameter::CertificateVerifiedOwnershipVerificationNecessary
+        {
+            return Err(DiagError::NotSupported);
+        }
+        let proof = signer.sign_challenge(&verification.challenge_server)?;
+        self.proof_of_ownership(&proof, &signer.get_ephemeral_public_key()?)
+    }
+}
+
+#[cfg(test)]
+mod authentication_test {
+    use super::{
+        parse_certificate_verification_response, AuthenticationReturnParameter, AuthenticationTask,
+    };
+
+    #[test]
+    fn test_parse_certificate_verification_response() {
+        let res = parse_certificate_verification_response(
+            AuthenticationTask::VerifyCertificateUnidirectional,
+            &[0x69, 0x01, 0x11, 0x00, 0x02, 0xAA, 0xBB, 0x00, 0x00],
+        )
+        .unwrap();
+        assert_eq!(
+            res.return_parameter,
+            AuthenticationReturnParameter::CertificateVerifiedOwnershipVerificationNecessary
+        );
+        assert_eq!(res.challenge_server, vec![0xAA, 0xBB]);
+        assert!(res.ephemeral_public_key_server.is_empty());
+
+        let res = parse_certificate_verification_response(
+            AuthenticationTask::VerifyCertificateBidirectional,
+            &[
+                0x69, 0x02, 0x11, 0x00, 0x01, 0xAA, 0x00, 0x01, 0xCC, 0x00, 0x01, 0xDD, 0x00, 0x00,
+            ],
+        )
+        .unwrap();
+        assert_eq!(res.certificate_server, vec![0xCC]);
+        assert_eq!(res.proof_of_ownership_server, vec![0xDD]);
+
+        // Challenge length exceeds the response length
+        assert!(parse_certificate_verification_response(
+            AuthenticationTask::VerifyCertificateUnidirectional,
+            &[0x69, 0x01, 0x11, 0x00, 0x04, 0xAA],
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Provides methods to stop and resume the updating of DTC status bits on the ECU
+
+use std::ops::{Deref, DerefMut};
+
+use log::warn;
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// DTC setting type
+pub enum DtcSettingType {
+    /// The ECU resumes updating the status bits of its DTCs
+    On,
+    /// The ECU stops updating the status bits of its DTCs
+    Off,
+}
+
+impl From<DtcSettingType> for u8 {
+    fn from(x: DtcSettingType) -> Self {
+        match x {
+            DtcSettingType::On => 0x01,
+            DtcSettingType::Off => 0x02,
+        }
+    }
+}
+
+impl UdsDiagnosticServer {
+    fn control_dtc_setting(
+        &mut self,
+        setting_type: DtcSettingType,
+        option_record: &[u8],
+    ) -> DiagServerResult<()> {
+        let mut args = Vec::with_capacity(option_record.len() + 1);
+        args.push(setting_type.into());
+        args.extend_from_slice(option_record);
+        let res = self.execute_command_with_response(UDSCommand::ControlDTCSettings, &args)?;
+        if res.len() < 2 {
+            // Require Positive SID, DTC setting type
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if res[1] & 0x7F != u8::from(setting_type) {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected DTC setting type 0x{:02X}, got 0x{:02X}",
+                u8::from(setting_type),
+                res[1]
+            )));
+        }
+        self.dtc_setting_disabled = setting_type == DtcSettingType::Off;
+        self.dtc_setting_option_record = if self.dtc_setting_disabled {
+            option_record.to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(())
+    }
+
+    /// Tells the ECU to resume updating the status bits of its DTCs
+    ///
+    /// ## Parameters
+    /// * option_record - Optional DTCSettingControlOptionRecord (For example, a DTC group).
+    ///   This can be empty
+    pub fn enable_dtc_setting(&mut self, option_record: &[u8]) -> DiagServerResult<()> {
+        self.control_dtc_setting(DtcSettingType::On, option_record)
+    }
+
+    /// Tells the ECU to stop updating the status bits of its DTCs. This prevents the ECU from
+    /// storing DTCs whilst it is being flashed or its components are being actuated.
+    ///
+    /// DTC setting is automatically enabled again (Using the same `option_record`) when the server
+    /// changes the ECU to [super::UDSSessionType::Default] using [UdsDiagnosticServer::set_session_mode].
+    /// The ECU enables DTC setting by itself when it is reset.
+    ///
+    /// ## Parameters
+    /// * option_record - Optional DTCSettingControlOptionRecord (For example, a DTC group).
+    ///   This can be empty
+    pub fn disable_dtc_setting(&mut self, option_record: &[u8]) -> DiagServerResult<()> {
+        self.control_dtc_setting(DtcSettingType::Off, option_record)
+    }
+
+    /// Tells the ECU to stop updating the status bits of its DTCs, and returns a guard which
+    /// enables DTC setting again with the same `option_record` once it goes out of scope.
+    /// The server can still be used through the guard.
+    ///
+    /// ## Parameters
+    /// * option_record - Optional DTCSettingControlOptionRecord (For example, a DTC group).
+    ///   This can be empty
+    pub fn disable_dtc_setting_scoped(
+        &mut self,
+        option_record: &[u8],
+    ) -> DiagServerResult<DtcSettingGuard<'_>> {
+        self.disable_dtc_setting(option_record)?;
+        Ok(DtcSettingGuard {
+            server: self,
+            option_record: option_record.to_vec(),
+        })
+    }
+
+    /// Returns true if DTC setting was disabled by this server, and has not been enabled since
+    pub fn is_dtc_setting_disabled(&self) -> bool {
+        self.dtc_setting_disabled
+    }
+}
+
+/// Guard returned by [UdsDiagnosticServer::disable_dtc_setting_scoped]. When this is dropped,
+/// DTC setting is enabled again on the ECU (Unless it has already been enabled).
+#[derive(Debug)]
+pub struct DtcSettingGuard<'a> {
+    server: &'a mut UdsDiagnosticServer,
+    option_record: Vec<u8>,
+}
+
+impl<'a> Deref for DtcSettingGuard<'a> {
+    type Target = UdsDiagnosticServer;
+
+    fn deref(&self) -> &Self::Target {
+        self.server
+    }
+}
+
+impl<'a> DerefMut for DtcSettingGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.server
+    }
+}
+
+impl<'a> Drop for DtcSettingGuard<'a> {
+    fn drop(&mut self) {
+        if self.server.dtc_setting_disabled {
+            if let Err(e) = self.server.enable_dtc_setting(&self.option_record) {
+                warn!("Could not enable DTC setting: {}", e)
+            }
+        }
+    }
+}
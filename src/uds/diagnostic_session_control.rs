@@ -1,5 +1,7 @@
 //!  Provides methods to manipulate the ECUs diagnostic session mode
 
+use log::warn;
+
 use crate::{DiagServerResult, DiagnosticServer};
 
 use super::{UDSCommand, UdsDiagnosticServer};
@@ -39,12 +41,29 @@ impl From<UDSSessionType> for u8 {
 }
 
 impl UdsDiagnosticServer {
-    /// Requests the ECU to go into a specific diagnostic session mode
+    /// Requests the ECU to go into a specific diagnostic session mode.
+    ///
+    /// If DTC setting was disabled with [UdsDiagnosticServer::disable_dtc_setting], then it is
+    /// enabled again (Using the same option record) prior to returning to [UDSSessionType::Default]. Any timing parameters
+    /// set with [UdsDiagnosticServer::set_timing_parameters] are discarded.
     pub fn set_session_mode(&mut self, session_mode: UDSSessionType) -> DiagServerResult<()> {
+        if session_mode == UDSSessionType::Default && self.dtc_setting_disabled {
+            let option_record = self.dtc_setting_option_record.clone();
+            if let Err(e) = self.enable_dtc_setting(&option_record) {
+                warn!(
+                    "Could not enable DTC setting prior to default session: {}",
+                    e
+                )
+            }
+        }
         self.execute_command_with_response(
             UDSCommand::DiagnosticSessionControl,
             &[session_mode.into()],
-        )
-        .map(|_| ())
+        )?;
+        if session_mode == UDSSessionType::Default {
+            // DTC setting is always enabled in the default session
+            self.dtc_setting_disabled = false;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,265 @@
+//! Provides methods for defining data identifiers on the ECU at runtime
+//!
+//! A dynamically defined data identifier (DDDID) combines parts of other DIDs and/or memory regions
+//! into a single DID, which can then be read with [UdsDiagnosticServer::read_data_by_identifiers].
+//! This allows many signals to be read with a single request.
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{
+    AddressAndLengthFormatIdentifier, DidCodec, DidRequest, UDSCommand, UdsDiagnosticServer,
+};
+
+// DynamicallyDefineDataIdentifier sub functions
+const DEFINE_BY_IDENTIFIER: u8 = 0x01;
+const DEFINE_BY_MEMORY_ADDRESS: u8 = 0x02;
+const CLEAR_DYNAMICALLY_DEFINED_DATA_IDENTIFIER: u8 = 0x03;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Source of data for a dynamically defined data identifier
+pub enum DynamicDidSource {
+    /// Part of the data record of another DID
+    Identifier {
+        /// Source DID
+        did: u16,
+        /// Position of the first byte to use in the source DID's data record.
+        /// NOTE: The first byte of the data record is position 1
+        position: u8,
+        /// Number of bytes to use from the source DID's data record
+        size: u8,
+    },
+    /// A memory region on the ECU
+    MemoryAddress {
+        /// Address and length format identifier used to encode `address` and `size`
+        alfid: AddressAndLengthFormatIdentifier,
+        /// Address of the memory region
+        address: u64,
+        /// Size of the memory region
+        size: u64,
+    },
+}
+
+impl DynamicDidSource {
+    fn data_len(&self) -> usize {
+        match self {
+            DynamicDidSource::Identifier { size, .. } => *size as usize,
+            DynamicDidSource::MemoryAddress { size, .. } => *size as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Builder for a dynamically defined data identifier. The data record of the DID is made up
+/// of each source in the order they were added.
+///
+/// Once built, the DID is defined on the ECU using [UdsDiagnosticServer::define_dynamic_did]
+pub struct DynamicDidBuilder {
+    did: u16,
+    sources: Vec<DynamicDidSource>,
+}
+
+impl DynamicDidBuilder {
+    /// Creates a new builder for a dynamically defined data identifier
+    ///
+    /// ## Parameters
+    /// * did - The DID to define. The ECU defines which DIDs can be dynamically defined
+    ///   (Typically 0xF200-0xF3FF)
+    pub fn new(did: u16) -> Self {
+        Self {
+            did,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Adds part of the data record of another DID
+    ///
+    /// ## Parameters
+    /// * did - Source DID
+    /// * position - Position of the first byte to use in the source DID's data record (1 based)
+    /// * size - Number of bytes to use from the source DID's data record
+    pub fn add_identifier(mut self, did: u16, position: u8, size: u8) -> Self {
+        self.sources.push(DynamicDidSource::Identifier {
+            did,
+            position,
+            size,
+        });
+        self
+    }
+
+    /// Adds a memory region
+    ///
+    /// ## Parameters
+    /// * alfid - Address and length format identifier used to encode `address` and `size`
+    /// * address - Address of the memory region
+    /// * size - Size of the memory region
+    pub fn add_memory_address(
+        mut self,
+        alfid: AddressAndLengthFormatIdentifier,
+        address: u64,
+        size: u64,
+    ) -> Self {
+        self.sources.push(DynamicDidSource::MemoryAddress {
+            alfid,
+            address,
+            size,
+        });
+        self
+    }
+
+    /// Returns the DID being defined
+    pub fn get_did(&self) -> u16 {
+        self.did
+    }
+
+    /// Returns the sources of the DID
+    pub fn get_sources(&self) -> &[DynamicDidSource] {
+        &self.sources
+    }
+
+    /// Returns the total length of the data record of the DID
+    pub fn data_len(&self) -> usize {
+        self.sources.iter().map(|x| x.data_len()).sum()
+    }
+
+    /// Builds the arguments for each DynamicallyDefineDataIdentifier request required to define the DID.
+    /// Consecutive sources of the same type (And for memory addresses, the same format identifier)
+    /// are combined into a single request
+    pub(crate) fn build_requests(&self) -> DiagServerResult<Vec<Vec<u8>>> {
+        if self.sources.is_empty() {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let mut requests: Vec<Vec<u8>> = Vec::new();
+        let mut last: Option<DynamicDidSource> = None;
+        for source in &self.sources {
+            let same_request = match (last, source) {
+                (
+                    Some(DynamicDidSource::Identifier { .. }),
+                    DynamicDidSource::Identifier { .. },
+                ) => true,
+                (
+                    Some(DynamicDidSource::MemoryAddress { alfid: prev, .. }),
+                    DynamicDidSource::MemoryAddress { alfid, .. },
+                ) => prev == *alfid,
+                _ => false,
+            };
+            if !same_request {
+                let mut args = match source {
+                    DynamicDidSource::Identifier { .. } => vec![DEFINE_BY_IDENTIFIER],
+                    DynamicDidSource::MemoryAddress { .. } => vec![DEFINE_BY_MEMORY_ADDRESS],
+                };
+                args.push((self.did >> 8) as u8);
+                args.push(self.did as u8);
+                if let DynamicDidSource::MemoryAddress { alfid, .. } = source {
+                    args.push(u8::from(*alfid));
+                }
+                requests.push(args);
+            }
+            let args = requests.last_mut().unwrap();
+            match source {
+                DynamicDidSource::Identifier {
+                    did,
+                    position,
+                    size,
+                } => {
+                    if *position == 0 {
+                        return Err(DiagError::ParameterInvalid);
+                    }
+                    args.extend_from_slice(&[(did >> 8) as u8, *did as u8, *position, *size]);
+                }
+                DynamicDidSource::MemoryAddress {
+                    alfid,
+                    address,
+                    size,
+                } => {
+                    // Skip the format identifier, it is only sent once per request
+                    args.extend_from_slice(&alfid.encode(*address, *size)?[1..]);
+                }
+            }
+            last = Some(*source);
+        }
+        Ok(requests)
+    }
+}
+
+impl UdsDiagnosticServer {
+    /// Defines a dynamically defined data identifier on the ECU. If the DID is already defined,
+    /// the ECU appends the new sources to the existing definition, so use
+    /// [UdsDiagnosticServer::clear_dynamic_did] first to redefine a DID.
+    ///
+    /// ## Parameters
+    /// * builder - The definition of the DID
+    ///
+    /// ## Returns
+    /// A [DidRequest] for reading the DID using [UdsDiagnosticServer::read_data_by_identifiers].
+    /// If the DID was already defined prior to this, the codec of the request will not match
+    /// the length of the DID's data record.
+    pub fn define_dynamic_did(
+        &mut self,
+        builder: &DynamicDidBuilder,
+    ) -> DiagServerResult<DidRequest> {
+        for args in builder.build_requests()? {
+            self.execute_dynamic_did_request(&args)?;
+        }
+        Ok(DidRequest {
+            did: builder.did,
+            codec: Some(DidCodec::Bytes(builder.data_len())),
+        })
+    }
+
+    /// Clears a dynamically defined data identifier on the ECU
+    ///
+    /// ## Parameters
+    /// * did - The DID to clear. If [None], then all dynamically defined data identifiers are cleared
+    pub fn clear_dynamic_did(&mut self, did: Option<u16>) -> DiagServerResult<()> {
+        match did {
+            Some(did) => self.execute_dynamic_did_request(&[
+                CLEAR_DYNAMICALLY_DEFINED_DATA_IDENTIFIER,
+                (did >> 8) as u8,
+                did as u8,
+            ]),
+            None => self.execute_dynamic_did_request(&[CLEAR_DYNAMICALLY_DEFINED_DATA_IDENTIFIER]),
+        }
+    }
+
+    fn execute_dynamic_did_request(&mut self, args: &[u8]) -> DiagServerResult<()> {
+        let res =
+            self.execute_command_with_response(UDSCommand::DynamicallyDefineDataIdentifier, args)?;
+        // ECU echoes back the sub function and DID (If one was provided)
+        let echo_len = args.len().min(3);
+        if res.len() < echo_len + 1
+            || res[1] & 0x7F != args[0]
+            || res[2..=echo_len] != args[1..echo_len]
+        {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected {:02X?}, got {:02X?}",
+                &args[..echo_len],
+                &res[1..]
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod dynamically_define_data_identifier_test {
+    use super::{AddressAndLengthFormatIdentifier, DynamicDidBuilder};
+
+    #[test]
+    fn test_build_requests() {
+        let alfid = AddressAndLengthFormatIdentifier::new(4, 1).unwrap();
+        let builder = DynamicDidBuilder::new(0xF300)
+            .add_identifier(0x1234, 1, 2)
+            .add_identifier(0x5678, 3, 1)
+            .add_memory_address(alfid, 0x40001000, 4);
+        assert_eq!(builder.data_len(), 7);
+        let requests = builder.build_requests().unwrap();
+        assert_eq!(
+            requests,
+            vec![
+                vec![0x01, 0xF3, 0x00, 0x12, 0x34, 0x01, 0x02, 0x56, 0x78, 0x03, 0x01],
+                vec![0x02, 0xF3, 0x00, 0x14, 0x40, 0x00, 0x10, 0x00, 0x04],
+            ]
+        );
+        assert!(DynamicDidBuilder::new(0xF300).build_requests().is_err());
+    }
+}
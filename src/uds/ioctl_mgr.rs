@@ -0,0 +1,211 @@
+//! Wrapper for UDS IOCTL requests
+
+use log::warn;
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Input output control parameter
+pub enum IOControlParameter {
+    /// Gives control of the identifier back to the ECU
+    ReturnControlToECU,
+    /// Sets the identifier to its default value
+    ResetToDefault,
+    /// Freezes the identifier at its current value
+    FreezeCurrentState,
+    /// Sets the identifier to a value provided by the tester
+    ShortTermAdjustment,
+}
+
+impl From<IOControlParameter> for u8 {
+    fn from(x: IOControlParameter) -> Self {
+        match x {
+            IOControlParameter::ReturnControlToECU => 0x00,
+            IOControlParameter::ResetToDefault => 0x01,
+            IOControlParameter::FreezeCurrentState => 0x02,
+            IOControlParameter::ShortTermAdjustment => 0x03,
+        }
+    }
+}
+
+/// Parses a positive InputOutputControlByIdentifier response (including the SID)
+///
+/// ## Returns
+/// The controlStatusRecord of the response
+pub(crate) fn parse_ioctl_response(
+    did: u16,
+    param: IOControlParameter,
+    resp: &[u8],
+) -> DiagServerResult<Vec<u8>> {
+    if resp.len() < 4 {
+        // Require Positive SID, DID << 8, DID & 0xFF, control parameter
+        return Err(DiagError::InvalidResponseLength);
+    }
+    let did_response = (resp[1] as u16) << 8 | resp[2] as u16;
+    if did_response != did || resp[3] != u8::from(param) {
+        return Err(DiagError::MismatchedResponse(format!(
+            "Expected identifier 0x{:04X} (Parameter 0x{:02X}), got identifier 0x{:04X} (Parameter 0x{:02X})",
+            did,
+            u8::from(param),
+            did_response,
+            resp[3]
+        )));
+    }
+    Ok(resp[4..].to_vec())
+}
+
+/// Handler for Input output control by identifier requests (IOCTL)
+/// This allows for short term actuation's of components an ECU controls.
+///
+/// If the tester still has control of the identifier when the manager is dropped,
+/// control is automatically returned to the ECU.
+///
+/// USE WITH CAUTION!
+#[derive(Debug)]
+pub struct UdsIOCTLManager<'a> {
+    server: &'a mut UdsDiagnosticServer,
+    identifier: u16,
+    control_enable_mask: Option<Vec<u8>>,
+    has_control: bool,
+}
+
+impl<'a> UdsIOCTLManager<'a> {
+    /// Creates an IOCTL manager.
+    ///
+    /// NOTE: This does NOT change the diagnostic session of the ECU. Most ECUs
+    /// require extended diagnostic session for IOCTL requests.
+    ///
+    /// ## Parameters
+    /// * identifier - The 16 bit data identifier of the component or function to control
+    /// * server - UDS server reference
+    pub fn new(identifier: u16, server: &'a mut UdsDiagnosticServer) -> Self {
+        Self {
+            server,
+            identifier,
+            control_enable_mask: None,
+            has_control: false,
+        }
+    }
+
+    /// Sets the controlEnableMask which is sent with every request. This is used when an identifier
+    /// contains more than one parameter, to select which of the parameters are affected by the request.
+    /// The format of the mask is defined by the identifier.
+    ///
+    /// ## Parameters
+    /// * mask - The control enable mask, or [None] to not send a mask
+    pub fn set_control_enable_mask(&mut self, mask: Option<Vec<u8>>) {
+        self.control_enable_mask = mask
+    }
+
+    /// Returns true if the tester may have control of the identifier. This is set as soon as a
+    /// request which takes control is sent, and is only cleared once the ECU confirms it has
+    /// taken back control
+    pub fn has_control(&self) -> bool {
+        self.has_control
+    }
+
+    fn execute_ioctl(
+        &mut self,
+        param: IOControlParameter,
+        control_state: &[u8],
+    ) -> DiagServerResult<Vec<u8>> {
+        let mut args = vec![
+            (self.identifier >> 8) as u8,
+            self.identifier as u8,
+            param.into(),
+        ];
+        args.extend_from_slice(control_state);
+        if let Some(mask) = &self.control_enable_mask {
+            args.extend_from_slice(mask);
+        }
+        if param != IOControlParameter::ReturnControlToECU {
+            // The ECU may actuate the identifier even if its response is lost,
+            // so assume the tester has control until the ECU confirms otherwise
+            self.has_control = true;
+        }
+        let res = self
+            .server
+            .execute_command_with_response(UDSCommand::InputOutputControlByIdentifier, &args)?;
+        let status = parse_ioctl_response(self.identifier, param, &res)?;
+        if param == IOControlParameter::ReturnControlToECU {
+            self.has_control = false;
+        }
+        Ok(status)
+    }
+
+    /// Asks the ECU to take back control of the identifier
+    ///
+    /// ## Returns
+    /// The controlStatusRecord returned by the ECU
+    pub fn return_control_to_ecu(&mut self) -> DiagServerResult<Vec<u8>> {
+        self.execute_ioctl(IOControlParameter::ReturnControlToECU, &[])
+    }
+
+    /// Asks the ECU to set the identifier to its default value
+    ///
+    /// ## Returns
+    /// The controlStatusRecord returned by the ECU
+    pub fn reset_to_default(&mut self) -> DiagServerResult<Vec<u8>> {
+        self.execute_ioctl(IOControlParameter::ResetToDefault, &[])
+    }
+
+    /// Asks the ECU to freeze the current state of the identifier
+    ///
+    /// ## Returns
+    /// The controlStatusRecord returned by the ECU
+    pub fn freeze_current_state(&mut self) -> DiagServerResult<Vec<u8>> {
+        self.execute_ioctl(IOControlParameter::FreezeCurrentState, &[])
+    }
+
+    /// Actuates the component at the provided identifier. This is a short term actuation.
+    /// Once the ECU looses power or returns to its default session state, the component will
+    /// be controlled by the ECU normally
+    ///
+    /// ## Parameters
+    /// * control_state - The value to set the identifier to
+    ///
+    /// ## Returns
+    /// The controlStatusRecord returned by the ECU
+    pub fn short_term_adjustment(&mut self, control_state: &[u8]) -> DiagServerResult<Vec<u8>> {
+        self.execute_ioctl(IOControlParameter::ShortTermAdjustment, control_state)
+    }
+}
+
+impl<'a> Drop for UdsIOCTLManager<'a> {
+    fn drop(&mut self) {
+        if self.has_control {
+            if let Err(e) = self.return_control_to_ecu() {
+                warn!(
+                    "Could not return control of identifier 0x{:04X} to the ECU: {}",
+                    self.identifier, e
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod ioctl_mgr_test {
+    use super::{parse_ioctl_response, IOControlParameter};
+
+    #[test]
+    fn test_parse_ioctl_response() {
+        assert_eq!(
+            parse_ioctl_response(
+                0x4101,
+                IOControlParameter::ShortTermAdjustment,
+                &[0x6F, 0x41, 0x01, 0x03, 0x64]
+            )
+            .unwrap(),
+            vec![0x64]
+        );
+        assert!(parse_ioctl_response(
+            0x4101,
+            IOControlParameter::FreezeCurrentState,
+            &[0x6F, 0x41, 0x01, 0x03, 0x64]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! Provides methods to change the baud rate of the communication link with the ECU
+//!
+//! Changing the baud rate is done in 2 steps:
+//! 1. Verify that the ECU can transition to the new baud rate using either
+//!    [UdsDiagnosticServer::verify_fixed_baudrate_transition] or [UdsDiagnosticServer::verify_specific_baudrate_transition]
+//! 2. Transition to the new baud rate using [UdsDiagnosticServer::transition_baudrate].
+//!    Once the ECU has been told to transition, the diagnostic server will close its channel,
+//!    reconfigure it with the new baud rate using [crate::channel::IsoTPChannel::set_iso_tp_cfg],
+//!    and open it again.
+//!
+//! When the ECU returns to the default session or is reset, it uses its default baud rate again,
+//! so the diagnostic server reconfigures its channel with the original settings.
+
+use crate::{
+    channel::{IsoTPChannel, IsoTPSettings},
+    DiagError, DiagServerResult, DiagnosticServer,
+};
+
+use super::{
+    UDSCommand, UdsCmd, UdsDiagnosticServer, UdsServerOptions, SUPPRESS_POSITIVE_RESPONSE,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Fixed baud rates defined by ISO14229
+pub enum FixedBaudrate {
+    /// Programming setup 9600 baud
+    PC9600,
+    /// Programming setup 19200 baud
+    PC19200,
+    /// Programming setup 38400 baud
+    PC38400,
+    /// Programming setup 57600 baud
+    PC57600,
+    /// Programming setup 115200 baud
+    PC115200,
+    /// CAN 125kbps
+    CAN125000,
+    /// CAN 250kbps
+    CAN250000,
+    /// CAN 500kbps
+    CAN500000,
+    /// CAN 1Mbps
+    CAN1000000,
+}
+
+impl FixedBaudrate {
+    /// Returns the baud rate in bits per second
+    pub fn get_baud(&self) -> u32 {
+        match self {
+            FixedBaudrate::PC9600 => 9600,
+            FixedBaudrate::PC19200 => 19200,
+            FixedBaudrate::PC38400 => 38400,
+            FixedBaudrate::PC57600 => 57600,
+            FixedBaudrate::PC115200 => 115200,
+            FixedBaudrate::CAN125000 => 125_000,
+            FixedBaudrate::CAN250000 => 250_000,
+            FixedBaudrate::CAN500000 => 500_000,
+            FixedBaudrate::CAN1000000 => 1_000_000,
+        }
+    }
+
+    pub(crate) fn from_identifier(x: u8) -> Option<Self> {
+        match x {
+            0x01 => Some(FixedBaudrate::PC9600),
+            0x02 => Some(FixedBaudrate::PC19200),
+            0x03 => Some(FixedBaudrate::PC38400),
+            0x04 => Some(FixedBaudrate::PC57600),
+            0x05 => Some(FixedBaudrate::PC115200),
+            0x10 => Some(FixedBaudrate::CAN125000),
+            0x11 => Some(FixedBaudrate::CAN250000),
+            0x12 => Some(FixedBaudrate::CAN500000),
+            0x13 => Some(FixedBaudrate::CAN1000000),
+            _ => None,
+        }
+    }
+}
+
+impl From<FixedBaudrate> for u8 {
+    fn from(x: FixedBaudrate) -> Self {
+        match x {
+            FixedBaudrate::PC9600 => 0x01,
+            FixedBaudrate::PC19200 => 0x02,
+            FixedBaudrate::PC38400 => 0x03,
+            FixedBaudrate::PC57600 => 0x04,
+            FixedBaudrate::PC115200 => 0x05,
+            FixedBaudrate::CAN125000 => 0x10,
+            FixedBaudrate::CAN250000 => 0x11,
+            FixedBaudrate::CAN500000 => 0x12,
+            FixedBaudrate::CAN1000000 => 0x13,
+        }
+    }
+}
+
+// Link control sub functions
+const VERIFY_FIXED_BAUDRATE: u8 = 0x01;
+const VERIFY_SPECIFIC_BAUDRATE: u8 = 0x02;
+const TRANSITION_BAUDRATE: u8 = 0x03;
+
+/// Tracks the state of baud rate transitions on the diagnostic server thread
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct LinkState {
+    original_cfg: IsoTPSettings,
+    channel_cfg: IsoTPSettings,
+    pending_baud: Option<u32>,
+}
+
+impl LinkState {
+    pub(crate) fn new(channel_cfg: IsoTPSettings) -> Self {
+        Self {
+            original_cfg: channel_cfg,
+            channel_cfg,
+            pending_baud: None,
+        }
+    }
+
+    fn reconfigure<C: IsoTPChannel>(
+        &mut self,
+        cfg: IsoTPSettings,
+        settings: &UdsServerOptions,
+        channel: &mut C,
+    ) -> DiagServerResult<()> {
+        self.channel_cfg = cfg;
+        channel.close()?;
+        channel.set_iso_tp_cfg(cfg)?;
+        channel.set_ids(settings.send_id, settings.recv_id)?;
+        channel.open()?;
+        Ok(())
+    }
+
+    /// Called by the diagnostic server thread after the ECU has returned to the default session,
+    /// or has been reset. The ECU then uses its default baud rate again, so the channel is
+    /// reconfigured with its original settings if the baud rate was changed
+    pub(crate) fn on_session_change<C: IsoTPChannel>(
+        &mut self,
+        settings: &UdsServerOptions,
+        channel: &mut C,
+    ) -> DiagServerResult<()> {
+        self.pending_baud = None;
+        if self.channel_cfg.can_speed != self.original_cfg.can_speed {
+            self.reconfigure(self.original_cfg, settings, channel)?;
+        }
+        Ok(())
+    }
+
+    /// Called by the diagnostic server thread after a LinkControl request was successfully sent.
+    /// Verification requests store the baud rate, and transition requests reconfigure the channel
+    pub(crate) fn on_link_control<C: IsoTPChannel>(
+        &mut self,
+        cmd: &UdsCmd,
+        settings: &UdsServerOptions,
+        channel: &mut C,
+    ) -> DiagServerResult<()> {
+        match cmd.bytes.get(1).map(|x| x & 0x7F) {
+            Some(VERIFY_FIXED_BAUDRATE) => {
+                self.pending_baud = cmd
+                    .bytes
+                    .get(2)
+                    .and_then(|x| FixedBaudrate::from_identifier(*x))
+                    .map(|x| x.get_baud());
+            }
+            Some(VERIFY_SPECIFIC_BAUDRATE) if cmd.bytes.len() >= 5 => {
+                self.pending_baud = Some(
+                    (cmd.bytes[2] as u32) << 16 | (cmd.bytes[3] as u32) << 8 | cmd.bytes[4] as u32,
+                );
+            }
+            Some(TRANSITION_BAUDRATE) => {
+                if let Some(baud) = self.pending_baud.take() {
+                    let mut cfg = self.channel_cfg;
+                    cfg.can_speed = baud;
+                    self.reconfigure(cfg, settings, channel)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl UdsDiagnosticServer {
+    /// Verifies if the ECU can transition to one of the fixed baud rates defined by ISO14229
+    ///
+    /// ## Parameters
+    /// * baud - The baud rate to transition to
+    pub fn verify_fixed_baudrate_transition(
+        &mut self,
+        baud: FixedBaudrate,
+    ) -> DiagServerResult<()> {
+        self.execute_link_control(&[VERIFY_FIXED_BAUDRATE, baud.into()])
+    }
+
+    /// Verifies if the ECU can transition to a specific baud rate
+    ///
+    /// ## Parameters
+    /// * baud - The baud rate to transition to in bits per second. The maximum value is 0xFFFFFF
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `baud` is larger than 0xFFFFFF
+    pub fn verify_specific_baudrate_transition(&mut self, baud: u32) -> DiagServerResult<()> {
+        if baud > 0xFFFFFF {
+            return Err(DiagError::ParameterInvalid);
+        }
+        self.execute_link_control(&[
+            VERIFY_SPECIFIC_BAUDRATE,
+            (baud >> 16) as u8,
+            (baud >> 8) as u8,
+            baud as u8,
+        ])
+    }
+
+    /// Tells the ECU to transition to the baud rate which was previously verified. The ECU does not send
+    /// a positive response to this request. The diagnostic server then reconfigures its channel with the new baud rate,
+    /// unless the ECU responded negatively.
+    ///
+    /// If no baud rate transition was successfully verified prior to this, the channel is not reconfigured.
+    pub fn transition_baudrate(&mut self) -> DiagServerResult<()> {
+        self.execute_command(
+            UDSCommand::LinkControl,
+            &[TRANSITION_BAUDRATE | SUPPRESS_POSITIVE_RESPONSE],
+        )
+    }
+
+    fn execute_link_control(&mut self, args: &[u8]) -> DiagServerResult<()> {
+        let res = self.execute_command_with_response(UDSCommand::LinkControl, args)?;
+        if res.len() < 2 {
+            // Require Positive SID, link control type
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if res[1] & 0x7F != args[0] {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected link control type 0x{:02X}, got 0x{:02X}",
+                args[0], res[1]
+            )));
+        }
+        Ok(())
+    }
+}
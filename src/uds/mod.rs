@@ -12,28 +12,59 @@ use std::{
 };
 
 use crate::{
-    channel::IsoTPChannel, channel::IsoTPSettings, dtc::DTCFormatType, helpers, BaseServerPayload,
-    BaseServerSettings, DiagError, DiagServerResult, DiagnosticServer, ServerEvent,
-    ServerEventHandler,
+    channel::IsoTPChannel, channel::IsoTPSettings, dtc::DTCFormatType, dtc::DtcStatusMask, helpers,
+    BaseServerPayload, BaseServerSettings, DiagError, DiagServerResult, DiagnosticServer,
+    ServerEvent, ServerEventHandler,
 };
 
 mod access_timing_parameter;
+mod authentication;
 mod clear_diagnostic_information;
 mod communication_control;
+mod control_dtc_setting;
 mod diagnostic_session_control;
+mod dynamically_define_data_identifier;
 mod ecu_reset;
+mod ioctl_mgr;
+mod link_control;
+mod read_data_by_identifier;
+mod read_data_by_periodic_identifier;
 mod read_dtc_information;
+mod read_memory_by_address;
+mod request_download;
+mod request_file_transfer;
+mod request_transfer_exit;
+mod request_upload;
+mod routine_control;
 mod scaling_data;
 mod security_access;
+mod transfer_data;
+mod write_data_by_identifier;
+mod write_memory_by_address;
 
 pub use access_timing_parameter::*;
+pub use authentication::*;
 pub use clear_diagnostic_information::*;
 pub use communication_control::*;
+pub use control_dtc_setting::*;
 pub use diagnostic_session_control::*;
+pub use dynamically_define_data_identifier::*;
 pub use ecu_reset::*;
+pub use ioctl_mgr::*;
+pub use link_control::*;
+pub use read_data_by_identifier::*;
+pub use read_data_by_periodic_identifier::*;
 pub use read_dtc_information::*;
+pub use request_download::*;
+pub use request_file_transfer::*;
+pub use routine_control::*;
 pub use scaling_data::*;
 pub use security_access::*;
+pub use transfer_data::*;
+
+/// suppressPosRspMsgIndicationBit. When this bit of a request's sub function is set,
+/// the ECU does not send a positive response to the request
+pub const SUPPRESS_POSITIVE_RESPONSE: u8 = 0x80;
 
 /// UDS Command Service IDs
 #[allow(missing_docs)]
@@ -48,6 +79,8 @@ pub enum UDSCommand {
     SecurityAccess,
     /// Controls communication functionality of the ECU.
     CommunicationControl,
+    /// Certificate based authentication (ISO14229-1:2020).
+    Authentication,
     /// Tester present command. Used internally by UDS Server
     TesterPresent,
     /// Accesses ECU timing parameters.
@@ -72,6 +105,8 @@ pub enum UDSCommand {
     RequestUpload,
     TransferData,
     RequestTransferExit,
+    /// Requests a file transfer to or from the ECU's file system.
+    RequestFileTransfer,
     Other(u8),
 }
 
@@ -82,6 +117,7 @@ impl From<u8> for UDSCommand {
             0x11 => UDSCommand::ECUReset,
             0x27 => UDSCommand::SecurityAccess,
             0x28 => UDSCommand::CommunicationControl,
+            0x29 => UDSCommand::Authentication,
             0x3E => UDSCommand::TesterPresent,
             0x83 => UDSCommand::AccessTimingParameters,
             0x84 => UDSCommand::SecuredDataTransmission,
@@ -103,6 +139,7 @@ impl From<u8> for UDSCommand {
             0x35 => UDSCommand::RequestUpload,
             0x36 => UDSCommand::TransferData,
             0x37 => UDSCommand::RequestTransferExit,
+            0x38 => UDSCommand::RequestFileTransfer,
             _ => UDSCommand::Other(sid),
         }
     }
@@ -115,6 +152,7 @@ impl From<UDSCommand> for u8 {
             UDSCommand::ECUReset => 0x11,
             UDSCommand::SecurityAccess => 0x27,
             UDSCommand::CommunicationControl => 0x28,
+            UDSCommand::Authentication => 0x29,
             UDSCommand::TesterPresent => 0x3E,
             UDSCommand::AccessTimingParameters => 0x83,
             UDSCommand::SecuredDataTransmission => 0x84,
@@ -136,11 +174,34 @@ impl From<UDSCommand> for u8 {
             UDSCommand::RequestUpload => 0x35,
             UDSCommand::TransferData => 0x36,
             UDSCommand::RequestTransferExit => 0x37,
+            UDSCommand::RequestFileTransfer => 0x38,
             UDSCommand::Other(s) => s,
         }
     }
 }
 
+impl UDSCommand {
+    /// Returns true if bit 7 of the service's sub function is the suppressPosRspMsgIndicationBit.
+    /// When this bit is set, the ECU does not send a positive response to the request
+    pub fn supports_suppress_positive_response(&self) -> bool {
+        matches!(
+            self,
+            UDSCommand::DiagnosticSessionControl
+                | UDSCommand::ECUReset
+                | UDSCommand::SecurityAccess
+                | UDSCommand::CommunicationControl
+                | UDSCommand::Authentication
+                | UDSCommand::TesterPresent
+                | UDSCommand::AccessTimingParameters
+                | UDSCommand::ControlDTCSettings
+                | UDSCommand::ResponseOnEvent
+                | UDSCommand::LinkControl
+                | UDSCommand::DynamicallyDefineDataIdentifier
+                | UDSCommand::RoutineControl
+        )
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(C)]
 /// UDS Error definitions
@@ -184,6 +245,34 @@ pub enum UDSError {
     /// The client has tried to request seed_key's too quickly, before the ECU timeout's period
     /// has expired
     RequiredTimeDelayNotExpired,
+    /// The ECU rejected the certificate as its validity period has expired or is not yet valid
+    CertificateVerificationFailedInvalidTimePeriod,
+    /// The ECU rejected the certificate as its signature is invalid
+    CertificateVerificationFailedInvalidSignature,
+    /// The ECU rejected the certificate as its chain of trust could not be verified
+    CertificateVerificationFailedInvalidChainOfTrust,
+    /// The ECU rejected the certificate as its type is invalid
+    CertificateVerificationFailedInvalidType,
+    /// The ECU rejected the certificate as its format is invalid
+    CertificateVerificationFailedInvalidFormat,
+    /// The ECU rejected the certificate as its content is invalid
+    CertificateVerificationFailedInvalidContent,
+    /// The ECU rejected the certificate as its scope is invalid
+    CertificateVerificationFailedInvalidScope,
+    /// The ECU rejected the certificate as it has been revoked
+    CertificateVerificationFailedInvalidCertificate,
+    /// The proof of ownership sent to the ECU could not be verified
+    OwnershipVerificationFailed,
+    /// The ECU failed to calculate the challenge
+    ChallengeCalculationFailed,
+    /// The ECU failed to set the access rights granted by the certificate
+    SettingAccessRightsFailed,
+    /// The ECU failed to create or derive the session key
+    SessionKeyCreationDerivationFailed,
+    /// The ECU failed to use the configuration data of the certificate
+    ConfigurationDataUsageFailed,
+    /// The ECU failed to de-authenticate the client
+    DeAuthenticationFailed,
     /// The ECU cannot accept the requested upload/download request due to a fault condition
     UploadDownloadNotAccepted,
     /// The ECU has halted data transfer due to a fault condition
@@ -249,6 +338,35 @@ fn lookup_uds_nrc(x: u8) -> String {
     format!("{:?}", UDSError::from(x))
 }
 
+/// Performs a command on the diagnostic server thread, using the timing parameters in `timing`
+/// if any have been adopted from the ECU. If periodic data identifiers are scheduled,
+/// any periodic data messages received whilst performing the command are passed to `periodic_state`
+fn perform_uds_cmd<C: IsoTPChannel>(
+    addr: u32,
+    cmd: &UdsCmd,
+    settings: &UdsServerOptions,
+    timing: &TimingState,
+    channel: &mut C,
+    periodic_state: &mut PeriodicState,
+) -> DiagServerResult<Vec<u8>> {
+    let settings = ActiveTimingSettings {
+        settings,
+        timing: timing.get(),
+    };
+    if !periodic_state.is_active() {
+        return helpers::perform_cmd(addr, cmd, &settings, channel, 0x21, lookup_uds_nrc);
+    }
+    helpers::perform_cmd_with_unsolicited(
+        addr,
+        cmd,
+        &settings,
+        channel,
+        0x21,
+        lookup_uds_nrc,
+        Some(&mut |x| periodic_state.handle_message(x)),
+    )
+}
+
 impl From<u8> for UDSError {
     fn from(p: u8) -> Self {
         match p {
@@ -267,6 +385,20 @@ impl From<u8> for UDSError {
             0x35 => Self::InvalidKey,
             0x36 => Self::ExceedNumberOfAttempts,
             0x37 => Self::RequiredTimeDelayNotExpired,
+            0x50 => Self::CertificateVerificationFailedInvalidTimePeriod,
+            0x51 => Self::CertificateVerificationFailedInvalidSignature,
+            0x52 => Self::CertificateVerificationFailedInvalidChainOfTrust,
+            0x53 => Self::CertificateVerificationFailedInvalidType,
+            0x54 => Self::CertificateVerificationFailedInvalidFormat,
+            0x55 => Self::CertificateVerificationFailedInvalidContent,
+            0x56 => Self::CertificateVerificationFailedInvalidScope,
+            0x57 => Self::CertificateVerificationFailedInvalidCertificate,
+            0x58 => Self::OwnershipVerificationFailed,
+            0x59 => Self::ChallengeCalculationFailed,
+            0x5A => Self::SettingAccessRightsFailed,
+            0x5B => Self::SessionKeyCreationDerivationFailed,
+            0x5C => Self::ConfigurationDataUsageFailed,
+            0x5D => Self::DeAuthenticationFailed,
             0x70 => Self::UploadDownloadNotAccepted,
             0x71 => Self::TransferDataSuspended,
             0x72 => Self::GeneralProgrammingFailure,
@@ -317,6 +449,8 @@ pub struct UdsServerOptions {
     /// Tester present minimum send interval in ms
     pub tester_present_interval_ms: u32,
     /// Configures if the diagnostic server will poll for a response from tester present.
+    /// If false, tester present is sent with the suppressPosRspMsgIndicationBit set, and the
+    /// server only listens for a negative response
     pub tester_present_require_response: bool,
 }
 
@@ -370,6 +504,16 @@ impl UdsCmd {
     pub fn get_uds_sid(&self) -> UDSCommand {
         self.bytes[0].into()
     }
+
+    /// Returns true if the command resets the ECU. Enabling (0x04) or disabling (0x05) rapid
+    /// power shutdown uses the same service, but does not reset the ECU
+    pub(crate) fn is_ecu_reset(&self) -> bool {
+        self.get_uds_sid() == UDSCommand::ECUReset
+            && !matches!(
+                self.bytes.get(1).map(|x| x & !SUPPRESS_POSITIVE_RESPONSE),
+                Some(0x04 | 0x05)
+            )
+    }
 }
 
 impl BaseServerPayload for UdsCmd {
@@ -388,6 +532,13 @@ impl BaseServerPayload for UdsCmd {
     fn requires_response(&self) -> bool {
         self.response_required
     }
+
+    fn suppress_positive_response(&self) -> bool {
+        !self.response_required
+            && self.bytes.len() > 1
+            && self.bytes[1] & SUPPRESS_POSITIVE_RESPONSE != 0
+            && self.get_uds_sid().supports_suppress_positive_response()
+    }
 }
 
 /// Base handler for UDS
@@ -409,6 +560,12 @@ pub struct UdsDiagnosticServer {
     repeat_count: u32,
     repeat_interval: std::time::Duration,
     dtc_format: Option<DTCFormatType>, // Used as a cache
+    dtc_status_availability_mask: Option<DtcStatusMask>, // Used as a cache
+    dtc_setting_disabled: bool,
+    dtc_setting_option_record: Vec<u8>,
+    periodic_rx: mpsc::Receiver<PeriodicDataRecord>,
+    timing: TimingState,
+    security_access_delay: std::time::Duration,
 }
 
 impl UdsDiagnosticServer {
@@ -442,10 +599,15 @@ impl UdsDiagnosticServer {
 
         let (tx_cmd, rx_cmd) = mpsc::channel::<UdsCmd>();
         let (tx_res, rx_res) = mpsc::channel::<DiagServerResult<Vec<u8>>>();
+        let (tx_periodic, rx_periodic) = mpsc::channel::<PeriodicDataRecord>();
+        let timing = TimingState::default();
+        let timing_t = timing.clone();
 
         std::thread::spawn(move || {
             let mut send_tester_present = false;
             let mut last_tester_present_time: Instant = Instant::now();
+            let mut link_state = LinkState::new(channel_cfg);
+            let mut periodic_state = PeriodicState::new(tx_periodic);
 
             event_handler.on_event(ServerEvent::ServerStart);
 
@@ -459,18 +621,24 @@ impl UdsDiagnosticServer {
                     // We have an incoming command
                     if cmd.get_uds_sid() == UDSCommand::DiagnosticSessionControl {
                         // Session change! Handle this differently
-                        match helpers::perform_cmd(
+                        match perform_uds_cmd(
                             settings.send_id,
                             &cmd,
                             &settings,
+                            &timing_t,
                             &mut server_channel,
-                            0x21,
-                            lookup_uds_nrc,
+                            &mut periodic_state,
                         ) {
                             // 0x78 - Response correctly received, response pending
                             Ok(res) => {
+                                // ECU stops sending periodic data and resets its timing parameters
+                                // when changing session
+                                periodic_state.on_session_change();
+                                timing_t.reset();
                                 // Set server session type
-                                if cmd.bytes[1] == u8::from(UDSSessionType::Default) {
+                                if cmd.bytes[1] & !SUPPRESS_POSITIVE_RESPONSE
+                                    == u8::from(UDSSessionType::Default)
+                                {
                                     // Default session, disable tester present
                                     send_tester_present = false;
                                 } else {
@@ -478,8 +646,16 @@ impl UdsDiagnosticServer {
                                     send_tester_present = true;
                                     last_tester_present_time = Instant::now();
                                 }
+                                let res = if send_tester_present {
+                                    Ok(res)
+                                } else {
+                                    // ECU returns to its default baud rate in the default session
+                                    link_state
+                                        .on_session_change(&settings, &mut server_channel)
+                                        .map(|_| res)
+                                };
                                 // Send response to client
-                                if tx_res.send(Ok(res)).is_err() {
+                                if tx_res.send(res).is_err() {
                                     // Terminate! Something has gone wrong and data can no longer be sent to client
                                     is_running_t.store(false, Ordering::Relaxed);
                                     event_handler.on_event(ServerEvent::CriticalError {
@@ -499,14 +675,38 @@ impl UdsDiagnosticServer {
                         }
                     } else {
                         // Generic command just perform it
-                        let res = helpers::perform_cmd(
+                        let mut res = perform_uds_cmd(
                             settings.send_id,
                             &cmd,
                             &settings,
+                            &timing_t,
                             &mut server_channel,
-                            0x21,
-                            lookup_uds_nrc,
+                            &mut periodic_state,
                         );
+                        if res.is_ok()
+                            && cmd.get_uds_sid() == UDSCommand::ReadDataByPeriodicIdentifier
+                        {
+                            periodic_state.on_request(&cmd);
+                        }
+                        if res.is_ok() && cmd.is_ecu_reset() {
+                            // ECU stops sending periodic data, and returns to its default timing
+                            // parameters and baud rate after resetting
+                            periodic_state.on_session_change();
+                            timing_t.reset();
+                            if let Err(e) =
+                                link_state.on_session_change(&settings, &mut server_channel)
+                            {
+                                res = Err(e);
+                            }
+                        }
+                        if res.is_ok() && cmd.get_uds_sid() == UDSCommand::LinkControl {
+                            // Baud rate may need changing
+                            if let Err(e) =
+                                link_state.on_link_control(&cmd, &settings, &mut server_channel)
+                            {
+                                res = Err(e);
+                            }
+                        }
                         event_handler.on_event(ServerEvent::Response(&res));
                         //event_handler.on_event(&res);
                         if tx_res.send(res).is_err() {
@@ -525,25 +725,43 @@ impl UdsDiagnosticServer {
                         >= settings.tester_present_interval_ms
                 {
                     // Send tester present message
-                    let cmd = UdsCmd::new(UDSCommand::TesterPresent, &[0x00], true);
+                    let cmd = if settings.tester_present_require_response {
+                        UdsCmd::new(UDSCommand::TesterPresent, &[0x00], true)
+                    } else {
+                        UdsCmd::new(
+                            UDSCommand::TesterPresent,
+                            &[SUPPRESS_POSITIVE_RESPONSE],
+                            false,
+                        )
+                    };
                     let addr = match settings.global_tp_id {
                         0 => settings.send_id,
                         x => x,
                     };
 
-                    if let Err(e) = helpers::perform_cmd(
+                    if let Err(e) = perform_uds_cmd(
                         addr,
                         &cmd,
                         &settings,
+                        &timing_t,
                         &mut server_channel,
-                        0x21,
-                        lookup_uds_nrc,
+                        &mut periodic_state,
                     ) {
                         event_handler.on_event(ServerEvent::TesterPresentError(e))
                     }
                     last_tester_present_time = Instant::now();
                 }
 
+                // Collect periodic data whilst idle
+                if periodic_state.is_active() {
+                    while let Ok(msg) = server_channel.read_bytes(0) {
+                        if msg.is_empty() {
+                            break;
+                        }
+                        periodic_state.handle_message(&msg);
+                    }
+                }
+
                 std::thread::sleep(std::time::Duration::from_millis(10));
             }
             // Goodbye server
@@ -561,6 +779,12 @@ impl UdsDiagnosticServer {
             repeat_count: 3,
             repeat_interval: std::time::Duration::from_millis(1000),
             dtc_format: None,
+            dtc_status_availability_mask: None,
+            dtc_setting_disabled: false,
+            dtc_setting_option_record: Vec::new(),
+            periodic_rx: rx_periodic,
+            timing,
+            security_access_delay: DEFAULT_SECURITY_ACCESS_DELAY,
         })
     }
 
@@ -569,12 +793,43 @@ impl UdsDiagnosticServer {
         self.settings
     }
 
+    /// Sends a command to the ECU with the suppressPosRspMsgIndicationBit set, so the ECU
+    /// does not send a positive response. Negative responses sent by the ECU within P2
+    /// are still returned as an error.
+    ///
+    /// ## Parameters
+    /// * sid - The Service ID of the command
+    /// * args - The arguments for the service. The first byte is the sub function
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if the service has no sub function
+    /// which supports suppressing the positive response
+    pub fn execute_command_suppress_positive_response(
+        &mut self,
+        sid: UDSCommand,
+        args: &[u8],
+    ) -> DiagServerResult<()> {
+        if args.is_empty() || !sid.supports_suppress_positive_response() {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let mut args = args.to_vec();
+        args[0] |= SUPPRESS_POSITIVE_RESPONSE;
+        self.execute_command(sid, &args)
+    }
+
     /// Internal command for sending UDS payload to the ECU
     fn exec_command(&mut self, cmd: UdsCmd) -> DiagServerResult<Vec<u8>> {
-        match self.tx.send(cmd) {
+        let is_ecu_reset = cmd.is_ecu_reset();
+        let res = match self.tx.send(cmd) {
             Ok(_) => self.rx.recv().unwrap_or(Err(DiagError::ServerNotRunning)),
             Err(_) => Err(DiagError::ServerNotRunning), // Server must have crashed!
+        };
+        if res.is_ok() && is_ecu_reset {
+            // ECU always starts with DTC setting enabled
+            self.dtc_setting_disabled = false;
+            self.dtc_setting_option_record.clear();
         }
+        res
     }
 }
 
@@ -591,12 +846,21 @@ impl DiagnosticServer<UDSCommand> for UdsDiagnosticServer {
     ///
     /// ## Returns
     /// If the function is successful, and the ECU responds with an OK response (Containing data),
-    /// then the full ECU response is returned. The response will begin with the sid + 0x40
+    /// then the full ECU response is returned. The response will begin with the sid + 0x40.
+    ///
+    /// [DiagError::ParameterInvalid] is returned if the suppressPosRspMsgIndicationBit is set in the
+    /// sub function, as the ECU would not send a response. Use
+    /// [UdsDiagnosticServer::execute_command_suppress_positive_response] instead
     fn execute_command_with_response(
         &mut self,
         sid: UDSCommand,
         args: &[u8],
     ) -> DiagServerResult<Vec<u8>> {
+        if sid.supports_suppress_positive_response()
+            && matches!(args.first(), Some(x) if x & SUPPRESS_POSITIVE_RESPONSE != 0)
+        {
+            return Err(DiagError::ParameterInvalid);
+        }
         let cmd = UdsCmd::new(sid, args, true);
 
         if self.repeat_count == 0 {
@@ -623,7 +887,10 @@ impl DiagnosticServer<UDSCommand> for UdsDiagnosticServer {
         }
     }
 
-    /// Send a command to the ECU, but don't receive a response
+    /// Send a command to the ECU, but don't receive a response.
+    ///
+    /// If the suppressPosRspMsgIndicationBit of the sub function is set, then the server
+    /// still listens for a negative response from the ECU, which is returned as an error.
     ///
     /// ## Parameters
     /// * sid - The Service ID of the command
@@ -643,7 +910,9 @@ impl DiagnosticServer<UDSCommand> for UdsDiagnosticServer {
         self.repeat_interval = std::time::Duration::from_millis(interval_ms as u64)
     }
 
-    /// Sends an arbitrary byte array to the ECU, and does not query response from the ECU
+    /// Sends an arbitrary byte array to the ECU, and does not query response from the ECU.
+    /// Like [DiagnosticServer::execute_command], negative responses are still returned
+    /// if the suppressPosRspMsgIndicationBit is set
     fn send_byte_array(&mut self, arr: &[u8]) -> DiagServerResult<()> {
         let cmd = UdsCmd::from_raw(arr, false);
         self.exec_command(cmd).map(|_| ())
@@ -0,0 +1,259 @@
+//! Provides methods for reading data from the ECU using 16 bit data identifiers (DIDs)
+
+use std::collections::HashMap;
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Describes how the data record of a DID should be decoded
+pub enum DidCodec {
+    /// Raw bytes of a fixed length. The data is returned as [DidValue::Bytes]
+    Bytes(usize),
+    /// ASCII text of a fixed length. The data is returned as [DidValue::Text]
+    Ascii(usize),
+    /// Big endian unsigned integer made up of 1-8 bytes. The data is returned as [DidValue::Unsigned]
+    Unsigned(u8),
+    /// Big endian signed integer made up of 1-8 bytes. The data is returned as [DidValue::Signed]
+    Signed(u8),
+    /// Big endian IEEE-754 single precision floating point number. The data is returned as [DidValue::Float]
+    Float,
+}
+
+impl DidCodec {
+    /// Returns the number of bytes the data record of the DID occupies
+    pub fn data_len(&self) -> usize {
+        match self {
+            DidCodec::Bytes(len) | DidCodec::Ascii(len) => *len,
+            DidCodec::Unsigned(num_bytes) | DidCodec::Signed(num_bytes) => *num_bytes as usize,
+            DidCodec::Float => 4,
+        }
+    }
+
+    /// Decodes the data record of a DID
+    ///
+    /// ## Returns
+    /// [DiagError::InvalidResponseLength] is returned if the length of `data` does not match
+    /// [DidCodec::data_len], and [DiagError::ParameterInvalid] is returned if an integer
+    /// codec is larger than 8 bytes.
+    pub fn decode(&self, data: &[u8]) -> DiagServerResult<DidValue> {
+        if data.len() != self.data_len() {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        match self {
+            DidCodec::Bytes(_) => Ok(DidValue::Bytes(data.to_vec())),
+            DidCodec::Ascii(_) => Ok(DidValue::Text(
+                String::from_utf8_lossy(data)
+                    .trim_end_matches(['\0', ' '])
+                    .to_string(),
+            )),
+            DidCodec::Unsigned(num_bytes) | DidCodec::Signed(num_bytes) => {
+                if *num_bytes == 0 || *num_bytes > 8 {
+                    return Err(DiagError::ParameterInvalid);
+                }
+                let raw = data.iter().fold(0u64, |acc, x| acc << 8 | *x as u64);
+                if let DidCodec::Signed(_) = self {
+                    // Sign extend from the top bit of the value
+                    let shift = 64 - 8 * *num_bytes as u32;
+                    Ok(DidValue::Signed(((raw << shift) as i64) >> shift))
+                } else {
+                    Ok(DidValue::Unsigned(raw))
+                }
+            }
+            DidCodec::Float => Ok(DidValue::Float(f32::from_be_bytes([
+                data[0], data[1], data[2], data[3],
+            ]))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// Decoded value of a DID data record
+pub enum DidValue {
+    /// Raw bytes
+    Bytes(Vec<u8>),
+    /// Text
+    Text(String),
+    /// Unsigned integer
+    Unsigned(u64),
+    /// Signed integer
+    Signed(i64),
+    /// Floating point number
+    Float(f32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A DID to request with [UdsDiagnosticServer::read_data_by_identifiers]
+pub struct DidRequest {
+    /// The 16 bit data identifier
+    pub did: u16,
+    /// Optional codec for decoding the data record of the DID.
+    ///
+    /// When reading more than one DID in a single request, every DID apart from the last
+    /// one needs a codec, as the ECU's response does not contain the length of each data record.
+    pub codec: Option<DidCodec>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// A single DID record from the ECU's response
+pub struct DidRecord {
+    /// The 16 bit data identifier
+    pub did: u16,
+    /// Raw data record of the DID
+    pub data: Vec<u8>,
+    /// Decoded value of the data record. This is only set if a codec was provided
+    /// in the [DidRequest]
+    pub value: Option<DidValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Definition of a DID, used by a [DidDecoder]
+pub struct DidDefinition {
+    /// Name of the DID
+    pub name: String,
+    /// Codec for decoding the data record of the DID
+    pub codec: DidCodec,
+}
+
+/// Looks up the definition of DIDs, so that data records containing multiple DIDs
+/// (Such as DTC snapshot records) can be split and decoded into named values.
+///
+/// This is implemented for [HashMap] with [DidDefinition] values
+pub trait DidDecoder {
+    /// Returns the definition of a DID, or [None] if the DID is unknown
+    fn get_did_definition(&self, did: u16) -> Option<DidDefinition>;
+}
+
+impl DidDecoder for HashMap<u16, DidDefinition> {
+    fn get_did_definition(&self, did: u16) -> Option<DidDefinition> {
+        self.get(&did).cloned()
+    }
+}
+
+/// Splits a positive ReadDataByIdentifier response (including the SID) into its DID records
+pub(crate) fn split_did_response(
+    dids: &[DidRequest],
+    resp: &[u8],
+) -> DiagServerResult<Vec<DidRecord>> {
+    if resp.is_empty() {
+        return Err(DiagError::EmptyResponse);
+    }
+    let mut pos = 1; // Skip SID
+    let mut result = Vec::with_capacity(dids.len());
+    for (idx, req) in dids.iter().enumerate() {
+        if resp.len() < pos + 2 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let did = (resp[pos] as u16) << 8 | resp[pos + 1] as u16;
+        if did != req.did {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected identifier 0x{:04X}, got identifier 0x{:04X}",
+                req.did, did
+            )));
+        }
+        pos += 2;
+        let len = match req.codec {
+            Some(codec) => codec.data_len(),
+            None if idx == dids.len() - 1 => resp.len() - pos, // Last DID takes the remaining data
+            None => return Err(DiagError::ParameterInvalid),
+        };
+        if resp.len() < pos + len {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let data = resp[pos..pos + len].to_vec();
+        pos += len;
+        let value = match req.codec {
+            Some(codec) => Some(codec.decode(&data)?),
+            None => None,
+        };
+        result.push(DidRecord { did, data, value })
+    }
+    if pos != resp.len() {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    Ok(result)
+}
+
+impl UdsDiagnosticServer {
+    /// Reads the data record of a single DID from the ECU
+    ///
+    /// ## Parameters
+    /// * did - The 16 bit data identifier to read
+    ///
+    /// ## Returns
+    /// If successful, this function returns the raw data stored at the identifier,
+    /// without the identifier itself
+    pub fn read_data_by_identifier(&mut self, did: u16) -> DiagServerResult<Vec<u8>> {
+        let mut records = self.read_data_by_identifiers(&[DidRequest { did, codec: None }])?;
+        Ok(records.remove(0).data)
+    }
+
+    /// Reads one or more DIDs from the ECU in a single request, and splits the ECU's response
+    /// into one record per requested DID.
+    ///
+    /// ## Parameters
+    /// * dids - List of DIDs to read. Every DID apart from the last one must have a [DidCodec]
+    ///   so that the response can be split correctly.
+    ///
+    /// ## Returns
+    /// The DID records in the same order as they were requested. If a DID in the response does not
+    /// match the requested DID, then [DiagError::MismatchedResponse] is returned.
+    pub fn read_data_by_identifiers(
+        &mut self,
+        dids: &[DidRequest],
+    ) -> DiagServerResult<Vec<DidRecord>> {
+        if dids.is_empty() || dids[..dids.len() - 1].iter().any(|x| x.codec.is_none()) {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let mut args = Vec::with_capacity(dids.len() * 2);
+        for x in dids {
+            args.push((x.did >> 8) as u8);
+            args.push(x.did as u8);
+        }
+        let resp = self.execute_command_with_response(UDSCommand::ReadDataByIdentifier, &args)?;
+        split_did_response(dids, &resp)
+    }
+}
+
+#[cfg(test)]
+mod read_data_by_identifier_test {
+    use super::{split_did_response, DidCodec, DidRequest, DidValue};
+
+    #[test]
+    fn test_split_multi_did_response() {
+        let dids = [
+            DidRequest {
+                did: 0xF190,
+                codec: Some(DidCodec::Ascii(4)),
+            },
+            DidRequest {
+                did: 0x1234,
+                codec: Some(DidCodec::Signed(2)),
+            },
+            DidRequest {
+                did: 0x5678,
+                codec: None,
+            },
+        ];
+        let resp = [
+            0x62, 0xF1, 0x90, b'W', b'D', b'B', 0x00, 0x12, 0x34, 0xFF, 0xFE, 0x56, 0x78, 0x01,
+            0x02, 0x03,
+        ];
+        let records = split_did_response(&dids, &resp).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].value, Some(DidValue::Text("WDB".into())));
+        assert_eq!(records[1].value, Some(DidValue::Signed(-2)));
+        assert_eq!(records[2].data, vec![0x01, 0x02, 0x03]);
+        assert_eq!(records[2].value, None);
+    }
+
+    #[test]
+    fn test_split_mismatched_did() {
+        let dids = [DidRequest {
+            did: 0xF190,
+            codec: None,
+        }];
+        assert!(split_did_response(&dids, &[0x62, 0xF1, 0x91, 0x00]).is_err());
+    }
+}
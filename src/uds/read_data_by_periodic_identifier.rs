@@ -0,0 +1,208 @@
+//! Provides methods for scheduling periodic transmission of data identifiers by the ECU
+//!
+//! Once scheduled, the ECU sends periodic data messages (Starting with 0x6A, followed by the
+//! periodic data identifier and its data record) on the same response ID as normal responses.
+//! These messages are collected by the diagnostic server and can be received using
+//! [UdsDiagnosticServer::receive_periodic_data].
+
+use std::{collections::BTreeSet, sync::mpsc, time::Duration};
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsCmd, UdsDiagnosticServer};
+
+/// Positive response SID of ReadDataByPeriodicIdentifier
+const PERIODIC_DATA_RESPONSE_SID: u8 = 0x6A;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Transmission mode of periodic data identifiers. The actual rate of each mode
+/// is defined by the vehicle manufacturer
+pub enum PeriodicTransmissionMode {
+    /// Send the data at a slow rate
+    SendAtSlowRate,
+    /// Send the data at a medium rate
+    SendAtMediumRate,
+    /// Send the data at a fast rate
+    SendAtFastRate,
+    /// Stop sending the data
+    StopSending,
+}
+
+impl From<PeriodicTransmissionMode> for u8 {
+    fn from(x: PeriodicTransmissionMode) -> Self {
+        match x {
+            PeriodicTransmissionMode::SendAtSlowRate => 0x01,
+            PeriodicTransmissionMode::SendAtMediumRate => 0x02,
+            PeriodicTransmissionMode::SendAtFastRate => 0x03,
+            PeriodicTransmissionMode::StopSending => 0x04,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A single periodic data message sent by the ECU
+pub struct PeriodicDataRecord {
+    /// Periodic data identifier. This is the low byte of the DID (0xF2xx)
+    pub periodic_id: u8,
+    /// Data record of the identifier
+    pub data: Vec<u8>,
+}
+
+impl PeriodicDataRecord {
+    /// Returns the full 16 bit DID of the periodic data identifier
+    pub fn get_did(&self) -> u16 {
+        0xF200 | self.periodic_id as u16
+    }
+}
+
+/// Tracks scheduled periodic data identifiers on the diagnostic server thread,
+/// and forwards periodic data messages to the server
+#[derive(Debug)]
+pub(crate) struct PeriodicState {
+    scheduled: BTreeSet<u8>,
+    sender: mpsc::Sender<PeriodicDataRecord>,
+}
+
+impl PeriodicState {
+    pub(crate) fn new(sender: mpsc::Sender<PeriodicDataRecord>) -> Self {
+        Self {
+            scheduled: BTreeSet::new(),
+            sender,
+        }
+    }
+
+    /// Returns true if the ECU has been told to send periodic data
+    pub(crate) fn is_active(&self) -> bool {
+        !self.scheduled.is_empty()
+    }
+
+    /// Called by the diagnostic server thread after a ReadDataByPeriodicIdentifier
+    /// request was successfully sent
+    pub(crate) fn on_request(&mut self, cmd: &UdsCmd) {
+        let ids = cmd.bytes.get(2..).unwrap_or_default();
+        match cmd.bytes.get(1).copied() {
+            Some(0x01..=0x03) => self.scheduled.extend(ids),
+            Some(0x04) if ids.is_empty() => self.scheduled.clear(),
+            Some(0x04) => ids.iter().for_each(|x| {
+                self.scheduled.remove(x);
+            }),
+            _ => {}
+        }
+    }
+
+    /// Called by the diagnostic server thread when the diagnostic session changes, as the
+    /// ECU stops sending all periodic data when changing sessions
+    pub(crate) fn on_session_change(&mut self) {
+        self.scheduled.clear()
+    }
+
+    /// Checks if a message from the ECU is a periodic data message, and forwards it to the server if it is.
+    ///
+    /// ## Returns
+    /// True if the message was a periodic data message
+    pub(crate) fn handle_message(&mut self, msg: &[u8]) -> bool {
+        // The positive response to the request itself is only the SID
+        if !self.is_active() || msg.len() < 2 || msg[0] != PERIODIC_DATA_RESPONSE_SID {
+            return false;
+        }
+        let _ = self.sender.send(PeriodicDataRecord {
+            periodic_id: msg[1],
+            data: msg[2..].to_vec(),
+        });
+        true
+    }
+}
+
+impl UdsDiagnosticServer {
+    /// Schedules (or stops) periodic transmission of data identifiers by the ECU
+    ///
+    /// ## Parameters
+    /// * mode - Transmission mode. Use [PeriodicTransmissionMode::StopSending] to stop transmission
+    /// * periodic_ids - List of periodic data identifiers. These are the low byte of the DID (0xF2xx).
+    ///   When stopping transmission, an empty list stops transmission of all identifiers
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `periodic_ids` is empty when
+    /// starting transmission
+    pub fn read_data_by_periodic_identifier(
+        &mut self,
+        mode: PeriodicTransmissionMode,
+        periodic_ids: &[u8],
+    ) -> DiagServerResult<()> {
+        if mode != PeriodicTransmissionMode::StopSending && periodic_ids.is_empty() {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let mut args = Vec::with_capacity(periodic_ids.len() + 1);
+        args.push(mode.into());
+        args.extend_from_slice(periodic_ids);
+        self.execute_command_with_response(UDSCommand::ReadDataByPeriodicIdentifier, &args)
+            .map(|_| ())
+    }
+
+    /// Stops periodic transmission of all data identifiers by the ECU
+    pub fn stop_all_periodic_data(&mut self) -> DiagServerResult<()> {
+        self.read_data_by_periodic_identifier(PeriodicTransmissionMode::StopSending, &[])
+    }
+
+    /// Waits for the next periodic data message sent by the ECU
+    ///
+    /// ## Parameters
+    /// * timeout - Maximum time to wait for a message. A duration of 0 only returns
+    ///   messages which have already been received
+    ///
+    /// ## Returns
+    /// The next periodic data message, or [None] if no message was received within `timeout`
+    pub fn receive_periodic_data(&mut self, timeout: Duration) -> Option<PeriodicDataRecord> {
+        if timeout.is_zero() {
+            self.periodic_rx.try_recv().ok()
+        } else {
+            self.periodic_rx.recv_timeout(timeout).ok()
+        }
+    }
+
+    /// Returns an iterator over all the periodic data messages which have been received by the
+    /// server so far, without waiting for new messages
+    pub fn drain_periodic_data(&mut self) -> mpsc::TryIter<'_, PeriodicDataRecord> {
+        self.periodic_rx.try_iter()
+    }
+}
+
+#[cfg(test)]
+mod read_data_by_periodic_identifier_test {
+    use std::sync::mpsc;
+
+    use super::{PeriodicState, UDSCommand, UdsCmd};
+
+    #[test]
+    fn test_periodic_message_handling() {
+        let (tx, rx) = mpsc::channel();
+        let mut state = PeriodicState::new(tx);
+        // Nothing scheduled, so nothing should be consumed
+        assert!(!state.handle_message(&[0x6A, 0x01, 0x02]));
+
+        state.on_request(&UdsCmd::new(
+            UDSCommand::ReadDataByPeriodicIdentifier,
+            &[0x03, 0x01, 0x02],
+            true,
+        ));
+        assert!(state.handle_message(&[0x6A, 0x01, 0xAA, 0xBB]));
+        assert!(!state.handle_message(&[0x6A])); // Response to the request
+        assert!(!state.handle_message(&[0x62, 0xF1, 0x90]));
+        let record = rx.try_recv().unwrap();
+        assert_eq!(record.get_did(), 0xF201);
+        assert_eq!(record.data, vec![0xAA, 0xBB]);
+
+        state.on_request(&UdsCmd::new(
+            UDSCommand::ReadDataByPeriodicIdentifier,
+            &[0x04, 0x01],
+            true,
+        ));
+        assert!(state.is_active());
+        state.on_request(&UdsCmd::new(
+            UDSCommand::ReadDataByPeriodicIdentifier,
+            &[0x04],
+            true,
+        ));
+        assert!(!state.is_active());
+    }
+}
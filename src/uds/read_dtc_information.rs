@@ -1,11 +1,11 @@
 //!  Provides methods to read and query DTCs on the ECU, as well as grabbing Env data about each DTC
 
 use crate::{
-    dtc::{self, DTCFormatType, DTCStatus, DTC},
+    dtc::{self, DTCFormatType, DtcStatusMask, DTC},
     DiagError, DiagServerResult, DiagnosticServer,
 };
 
-use super::{UDSCommand, UdsDiagnosticServer};
+use super::{DidDecoder, DidValue, UDSCommand, UdsDiagnosticServer};
 
 #[derive(Debug, Copy, Clone)]
 #[repr(u8)]
@@ -59,6 +59,347 @@ pub enum DtcSubFunction {
     ReportDTCFaultDetectionCounter = 0x14,
     /// This function take no additional arguments
     ReportDTCWithPermanentStatus = 0x15,
+
+    /// This function takes a 1 byte DTCExtDataRecordNumber
+    ReportDTCExtDataRecordByRecordNumber = 0x16,
+    /// This function takes a 1 byte DTCStatusMask and a 1 byte MemorySelection
+    ReportUserDefMemoryDTCByStatusMask = 0x17,
+    /// This function takes a 3 byte DTCMaskRecord, a 1 byte DTCSnapshotRecordNumber and a 1 byte MemorySelection
+    ReportUserDefMemoryDTCSnapshotRecordByDTCNumber = 0x18,
+    /// This function takes a 3 byte DTCMaskRecord, a 1 byte DTCExtDataRecordNumber and a 1 byte MemorySelection
+    ReportUserDefMemoryDTCExtDataRecordByDTCNumber = 0x19,
+    /// This function takes a 1 byte DTCExtDataRecordNumber
+    ReportSupportedDTCExtDataRecord = 0x1A,
+    /// This function takes a 1 byte FunctionalGroupIdentifier, a 1 byte DTCStatusMask and a 1 byte DTCSeverityMask
+    ReportWWHOBDDTCByMaskRecord = 0x42,
+    /// This function takes a 1 byte FunctionalGroupIdentifier
+    ReportWWHOBDDTCWithPermanentStatus = 0x55,
+    /// This function takes a 1 byte FunctionalGroupIdentifier and a 1 byte DTCReadinessGroupIdentifier
+    ReportDTCInformationByDTCReadinessGroupIdentifier = 0x56,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// WWH-OBD DTC class (GTR) of a DTC
+pub enum WwhObdDtcClass {
+    /// Class 0
+    Class0,
+    /// Class A
+    ClassA,
+    /// Class B1
+    ClassB1,
+    /// Class B2
+    ClassB2,
+    /// Class C
+    ClassC,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Severity of a DTC
+pub struct DtcSeverity {
+    /// The failure only needs to be repaired at the next scheduled maintenance
+    pub maintenance_only: bool,
+    /// The failure needs to be checked at the next halt of the vehicle
+    pub check_at_next_halt: bool,
+    /// The failure needs to be checked immediately
+    pub check_immediately: bool,
+    /// WWH-OBD DTC class. This is [None] if the ECU does not report a class
+    pub class: Option<WwhObdDtcClass>,
+}
+
+impl From<u8> for DtcSeverity {
+    fn from(x: u8) -> Self {
+        let class = match x & 0x1F {
+            0x01 => Some(WwhObdDtcClass::Class0),
+            0x02 => Some(WwhObdDtcClass::ClassA),
+            0x04 => Some(WwhObdDtcClass::ClassB1),
+            0x08 => Some(WwhObdDtcClass::ClassB2),
+            0x10 => Some(WwhObdDtcClass::ClassC),
+            _ => None,
+        };
+        Self {
+            maintenance_only: x & 0x20 != 0,
+            check_at_next_halt: x & 0x40 != 0,
+            check_immediately: x & 0x80 != 0,
+            class,
+        }
+    }
+}
+
+impl From<DtcSeverity> for u8 {
+    fn from(x: DtcSeverity) -> Self {
+        let class = match x.class {
+            Some(WwhObdDtcClass::Class0) => 0x01,
+            Some(WwhObdDtcClass::ClassA) => 0x02,
+            Some(WwhObdDtcClass::ClassB1) => 0x04,
+            Some(WwhObdDtcClass::ClassB2) => 0x08,
+            Some(WwhObdDtcClass::ClassC) => 0x10,
+            None => 0x00,
+        };
+        class
+            | (x.maintenance_only as u8) << 5
+            | (x.check_at_next_halt as u8) << 6
+            | (x.check_immediately as u8) << 7
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A DTC and its severity
+pub struct DtcSeverityRecord {
+    /// Severity of the DTC
+    pub severity: DtcSeverity,
+    /// Functional unit of the DTC. This is [None] for WWH-OBD DTCs, which do not report a functional unit
+    pub functional_unit: Option<u8>,
+    /// The DTC, including its status
+    pub dtc: DTC,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Identification of a DTC snapshot record stored on the ECU
+pub struct DtcSnapshotIdentification {
+    /// The raw value of the DTC
+    pub dtc: u32,
+    /// Number of the snapshot record
+    pub record_number: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// A single DID stored within a DTC snapshot record
+pub struct DtcSnapshotData {
+    /// The 16 bit data identifier
+    pub did: u16,
+    /// Name of the DID. This is only set if the [DidDecoder] knows the DID
+    pub name: Option<String>,
+    /// Raw data of the DID
+    pub data: Vec<u8>,
+    /// Decoded value of the DID. This is only set if the [DidDecoder] knows the DID
+    pub value: Option<DidValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// A DTC snapshot record (Freeze frame)
+pub struct DtcSnapshotRecord {
+    /// Number of the snapshot record
+    pub record_number: u8,
+    /// Number of DIDs the ECU reported to be in the record
+    pub number_of_identifiers: u8,
+    /// DIDs stored in the record
+    pub data: Vec<DtcSnapshotData>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// A DTC and its snapshot records
+pub struct DtcSnapshot {
+    /// The DTC, including its status
+    pub dtc: DTC,
+    /// Snapshot records stored for the DTC
+    pub records: Vec<DtcSnapshotRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A single DTC extended data record
+pub struct DtcExtendedDataRecord {
+    /// Number of the extended data record
+    pub record_number: u8,
+    /// Raw data of the record
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// A DTC and its extended data records
+pub struct DtcExtendedData {
+    /// The DTC, including its status
+    pub dtc: DTC,
+    /// Extended data records stored for the DTC
+    pub records: Vec<DtcExtendedDataRecord>,
+}
+
+/// Parses a DTC and its status (4 bytes) starting at `pos`
+fn parse_dtc_and_status(fmt: DTCFormatType, resp: &[u8], pos: usize) -> DiagServerResult<DTC> {
+    if resp.len() < pos + 4 {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    Ok(DTC::from_uds(
+        fmt,
+        (resp[pos] as u32) << 16 | (resp[pos + 1] as u32) << 8 | resp[pos + 2] as u32,
+        resp[pos + 3],
+    ))
+}
+
+/// Parses a list of DTCs and their status (4 bytes each) starting at `pos` until the end of the response
+fn parse_dtc_list(fmt: DTCFormatType, resp: &[u8], pos: usize) -> DiagServerResult<Vec<DTC>> {
+    let records = resp
+        .get(pos..)
+        .ok_or(DiagError::InvalidResponseLength)?
+        .chunks_exact(4);
+    if !records.remainder().is_empty() {
+        return Err(DiagError::InvalidResponseLength); // Each DTC should be 4 bytes!
+    }
+    records.map(|x| parse_dtc_and_status(fmt, x, 0)).collect()
+}
+
+/// Parses a list of DTC severity records starting at `pos` until the end of the response.
+/// If `has_functional_unit` is false, then each record is 5 bytes (WWH-OBD), otherwise each record is 6 bytes
+fn parse_severity_records(
+    fmt: DTCFormatType,
+    resp: &[u8],
+    pos: usize,
+    has_functional_unit: bool,
+) -> DiagServerResult<Vec<DtcSeverityRecord>> {
+    let record_len = if has_functional_unit { 6 } else { 5 };
+    let records = resp
+        .get(pos..)
+        .ok_or(DiagError::InvalidResponseLength)?
+        .chunks_exact(record_len);
+    if !records.remainder().is_empty() {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    records
+        .map(|x| {
+            Ok(DtcSeverityRecord {
+                severity: x[0].into(),
+                functional_unit: has_functional_unit.then(|| x[1]),
+                dtc: parse_dtc_and_status(fmt, x, record_len - 4)?,
+            })
+        })
+        .collect()
+}
+
+/// Parses the DIDs of a snapshot record starting at `pos`.
+///
+/// The length of each DID's data comes from `decoder`. If a DID is not known by the decoder,
+/// then it takes the remaining data of the response, as its length cannot be determined. Parsing
+/// then stops, so any remaining identifiers (And records) are part of that DID's raw data.
+fn parse_snapshot_data(
+    resp: &[u8],
+    pos: &mut usize,
+    number_of_identifiers: u8,
+    decoder: Option<&dyn DidDecoder>,
+) -> DiagServerResult<Vec<DtcSnapshotData>> {
+    let mut result = Vec::with_capacity(number_of_identifiers as usize);
+    for _ in 0..number_of_identifiers {
+        if resp.len() < *pos + 2 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let did = (resp[*pos] as u16) << 8 | resp[*pos + 1] as u16;
+        *pos += 2;
+        let definition = match decoder.and_then(|x| x.get_did_definition(did)) {
+            Some(d) => d,
+            None => {
+                // Length is unknown, so nothing after this DID can be parsed
+                result.push(DtcSnapshotData {
+                    did,
+                    name: None,
+                    data: resp[*pos..].to_vec(),
+                    value: None,
+                });
+                *pos = resp.len();
+                break;
+            }
+        };
+        let len = definition.codec.data_len();
+        if resp.len() < *pos + len {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let data = resp[*pos..*pos + len].to_vec();
+        *pos += len;
+        result.push(DtcSnapshotData {
+            did,
+            value: Some(definition.codec.decode(&data)?),
+            name: Some(definition.name),
+            data,
+        });
+    }
+    Ok(result)
+}
+
+/// Parses the response to ReportDTCSnapshotRecordByDTCNumber (Or its user defined memory variant),
+/// where the DTC and its status start at `dtc_pos`
+fn parse_snapshot_by_dtc_number(
+    fmt: DTCFormatType,
+    resp: &[u8],
+    dtc_pos: usize,
+    decoder: Option<&dyn DidDecoder>,
+) -> DiagServerResult<DtcSnapshot> {
+    let dtc = parse_dtc_and_status(fmt, resp, dtc_pos)?;
+    let mut pos = dtc_pos + 4;
+    let mut records = Vec::new();
+    while pos < resp.len() {
+        if resp.len() < pos + 2 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let record_number = resp[pos];
+        let number_of_identifiers = resp[pos + 1];
+        pos += 2;
+        records.push(DtcSnapshotRecord {
+            record_number,
+            number_of_identifiers,
+            data: parse_snapshot_data(resp, &mut pos, number_of_identifiers, decoder)?,
+        });
+    }
+    Ok(DtcSnapshot { dtc, records })
+}
+
+/// Parses the response to ReportDTCSnapshotRecordByRecordNumber
+fn parse_snapshot_by_record_number(
+    fmt: DTCFormatType,
+    resp: &[u8],
+    decoder: Option<&dyn DidDecoder>,
+) -> DiagServerResult<Vec<DtcSnapshot>> {
+    let mut pos = 2;
+    let mut result = Vec::new();
+    while pos < resp.len() {
+        // Record number, DTC and status, number of identifiers
+        if resp.len() < pos + 6 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let record_number = resp[pos];
+        let dtc = parse_dtc_and_status(fmt, resp, pos + 1)?;
+        let number_of_identifiers = resp[pos + 5];
+        pos += 6;
+        result.push(DtcSnapshot {
+            dtc,
+            records: vec![DtcSnapshotRecord {
+                record_number,
+                number_of_identifiers,
+                data: parse_snapshot_data(resp, &mut pos, number_of_identifiers, decoder)?,
+            }],
+        });
+    }
+    Ok(result)
+}
+
+/// Parses the response to ReportDTCExtendedDataRecordByDTCNumber (Or its mirror / user defined memory
+/// variants), where the DTC and its status start at `dtc_pos`.
+///
+/// The length of each record comes from `record_lengths`. If a record's length is not known,
+/// then it takes the remaining data of the response.
+fn parse_extended_data(
+    fmt: DTCFormatType,
+    resp: &[u8],
+    dtc_pos: usize,
+    record_lengths: &[(u8, usize)],
+) -> DiagServerResult<DtcExtendedData> {
+    let dtc = parse_dtc_and_status(fmt, resp, dtc_pos)?;
+    let mut pos = dtc_pos + 4;
+    let mut records = Vec::new();
+    while pos < resp.len() {
+        let record_number = resp[pos];
+        pos += 1;
+        let len = record_lengths
+            .iter()
+            .find(|(x, _)| *x == record_number)
+            .map(|(_, len)| *len)
+            .unwrap_or(resp.len() - pos);
+        if resp.len() < pos + len {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        records.push(DtcExtendedDataRecord {
+            record_number,
+            data: resp[pos..pos + len].to_vec(),
+        });
+        pos += len;
+    }
+    Ok(DtcExtendedData { dtc, records })
 }
 
 impl UdsDiagnosticServer {
@@ -67,18 +408,18 @@ impl UdsDiagnosticServer {
     ///
     /// ## Returns
     /// Returns a tuple of the given information:
-    /// 1. (u8) - DTCStatusAvailabilityMask
+    /// 1. ([DtcStatusMask]) - DTCStatusAvailabilityMask
     /// 2. ([DTCFormatType]) - Format of the DTCs
     /// 3. (u16) - Number of DTCs which match the status mask
     pub fn get_number_of_dtcs_by_status_mask(
         &mut self,
-        status_mask: u8,
-    ) -> DiagServerResult<(u8, DTCFormatType, u16)> {
+        status_mask: DtcStatusMask,
+    ) -> DiagServerResult<(DtcStatusMask, DTCFormatType, u16)> {
         let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
                 DtcSubFunction::ReportNumberOfDTCByStatusMask as u8,
-                status_mask,
+                status_mask.into(),
             ],
         )?;
 
@@ -86,8 +427,9 @@ impl UdsDiagnosticServer {
             Err(DiagError::InvalidResponseLength)
         } else {
             self.dtc_format = Some(dtc::dtc_format_from_uds(resp[3]));
+            self.dtc_status_availability_mask = Some(DtcStatusMask(resp[2]));
             Ok((
-                resp[2],
+                DtcStatusMask(resp[2]),
                 dtc::dtc_format_from_uds(resp[3]),
                 (resp[4] as u16) << 8 | resp[5] as u16,
             ))
@@ -96,95 +438,26 @@ impl UdsDiagnosticServer {
 
     /// Returns a list of DTCs stored on the ECU
     /// matching the provided status_mask
-    pub fn get_dtcs_by_status_mask(&mut self, status_mask: u8) -> DiagServerResult<Vec<DTC>> {
-        let mut resp = self.execute_command_with_response(
-            UDSCommand::ReadDTCInformation,
-            &[DtcSubFunction::ReportDTCByStatusMask as u8, status_mask],
-        )?;
-        if resp.len() < 7 {
-            return Ok(vec![]); // No errors
-        }
-
-        resp.drain(0..3);
-        if resp.len() % 4 != 0 {
-            return Err(DiagError::InvalidResponseLength); // Each DTC should be 4 bytes!
-        }
-
-        // Now, see if we can query the ECU's DTC format
-        // Note the ECU might not support this command, in which case return 0 as format specifier
-        let fmt = match self.dtc_format {
-            Some(s) => s,
-            None => self
-                .get_number_of_dtcs_by_status_mask(status_mask)
-                .map(|r| r.1)
-                .unwrap_or(DTCFormatType::Unknown(0)),
-        };
-        let mut result: Vec<DTC> = Vec::new();
-
-        for x in (0..resp.len()).step_by(4) {
-            let dtc_code: u32 =
-                (resp[x] as u32) << 16 | (resp[x + 1] as u32) << 8 | resp[x + 2] as u32;
-            let status = resp[x + 3];
-
-            result.push(DTC {
-                format: fmt,
-                raw: dtc_code,
-                status: DTCStatus::Unknown(status), // TODO
-                mil_on: status & 0b10000000 != 0,
-                readiness_flag: false,
-            })
-        }
-
-        Ok(result)
+    pub fn get_dtcs_by_status_mask(
+        &mut self,
+        status_mask: DtcStatusMask,
+    ) -> DiagServerResult<Vec<DTC>> {
+        self.get_dtc_list(&[
+            DtcSubFunction::ReportDTCByStatusMask as u8,
+            status_mask.into(),
+        ])
     }
 
     /// Returns a list of DTCs out of the DTC mirror memory who's status_mask matches
     /// the provided mask
     pub fn get_mirror_memory_dtcs_by_status_mask(
         &mut self,
-        status_mask: u8,
+        status_mask: DtcStatusMask,
     ) -> DiagServerResult<Vec<DTC>> {
-        let mut resp = self.execute_command_with_response(
-            UDSCommand::ReadDTCInformation,
-            &[
-                DtcSubFunction::ReportMirrorMemoryDTCByStatusMask as u8,
-                status_mask,
-            ],
-        )?;
-        if resp.len() < 7 {
-            return Ok(vec![]); // No errors
-        }
-
-        resp.drain(0..3);
-        if resp.len() % 4 != 0 {
-            return Err(DiagError::InvalidResponseLength); // Each DTC should be 4 bytes!
-        }
-
-        // Now, see if we can query the ECU's DTC format
-        // Note the ECU might not support this command, in which case return 0 as format specifier
-        let fmt = match self.dtc_format {
-            Some(s) => s,
-            None => self
-                .get_number_of_dtcs_by_status_mask(status_mask)
-                .map(|r| r.1)
-                .unwrap_or(DTCFormatType::Unknown(0)),
-        };
-        let mut result: Vec<DTC> = Vec::new();
-
-        for x in (0..resp.len()).step_by(4) {
-            let dtc_code: u32 =
-                (resp[x] as u32) << 16 | (resp[x + 1] as u32) << 8 | resp[x + 2] as u32;
-            let status = resp[x + 3];
-
-            result.push(DTC {
-                format: fmt,
-                raw: dtc_code,
-                status: DTCStatus::Unknown(status), // TODO
-                mil_on: status & 0b10000000 != 0,
-                readiness_flag: false,
-            })
-        }
-        Ok(result)
+        self.get_dtc_list(&[
+            DtcSubFunction::ReportMirrorMemoryDTCByStatusMask as u8,
+            status_mask.into(),
+        ])
     }
 
     /// Returns the number of DTCs in DTC mirror memory who's status_mask matches
@@ -192,26 +465,27 @@ impl UdsDiagnosticServer {
     ///
     /// ## Returns
     /// Returns a tuple of the given information:
-    /// 1. (u8) - DTCStatusAvailabilityMask
+    /// 1. ([DtcStatusMask]) - DTCStatusAvailabilityMask
     /// 2. ([DTCFormatType]) - Format of the DTCs
     /// 3. (u16) - Number of DTCs which match the status mask
     pub fn get_number_of_mirror_memory_dtcs_by_status_mask(
         &mut self,
-        status_mask: u8,
-    ) -> DiagServerResult<(u8, DTCFormatType, u16)> {
+        status_mask: DtcStatusMask,
+    ) -> DiagServerResult<(DtcStatusMask, DTCFormatType, u16)> {
         let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
                 DtcSubFunction::ReportNumberOfMirrorMemoryDTCByStatusMask as u8,
-                status_mask,
+                status_mask.into(),
             ],
         )?;
         if resp.len() != 6 {
             Err(DiagError::InvalidResponseLength)
         } else {
             self.dtc_format = Some(dtc::dtc_format_from_uds(resp[3]));
+            self.dtc_status_availability_mask = Some(DtcStatusMask(resp[2]));
             Ok((
-                resp[2],
+                DtcStatusMask(resp[2]),
                 dtc::dtc_format_from_uds(resp[3]),
                 (resp[4] as u16) << 8 | resp[5] as u16,
             ))
@@ -223,26 +497,27 @@ impl UdsDiagnosticServer {
     ///
     /// ## Returns
     /// Returns a tuple of the given information:
-    /// 1. (u8) - DTCStatusAvailabilityMask
+    /// 1. ([DtcStatusMask]) - DTCStatusAvailabilityMask
     /// 2. ([DTCFormatType]) - Format of the DTCs
     /// 3. (u16) - Number of DTCs which match the status mask
     pub fn get_number_of_emissions_related_obd_dtcs_by_status_mask(
         &mut self,
-        status_mask: u8,
-    ) -> DiagServerResult<(u8, DTCFormatType, u16)> {
+        status_mask: DtcStatusMask,
+    ) -> DiagServerResult<(DtcStatusMask, DTCFormatType, u16)> {
         let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
                 DtcSubFunction::ReportNumberOfEmissionsRelatedOBDDTCByStatusMask as u8,
-                status_mask,
+                status_mask.into(),
             ],
         )?;
         if resp.len() != 6 {
             Err(DiagError::InvalidResponseLength)
         } else {
             self.dtc_format = Some(dtc::dtc_format_from_uds(resp[3]));
+            self.dtc_status_availability_mask = Some(DtcStatusMask(resp[2]));
             Ok((
-                resp[2],
+                DtcStatusMask(resp[2]),
                 dtc::dtc_format_from_uds(resp[3]),
                 (resp[4] as u16) << 8 | resp[5] as u16,
             ))
@@ -253,57 +528,67 @@ impl UdsDiagnosticServer {
     /// who's status mask matches the provided mask
     pub fn get_emissions_related_obd_dtcs_by_status_mask(
         &mut self,
-        status_mask: u8,
+        status_mask: DtcStatusMask,
     ) -> DiagServerResult<Vec<DTC>> {
-        let mut resp = self.execute_command_with_response(
-            UDSCommand::ReadDTCInformation,
-            &[
-                DtcSubFunction::ReportEmissionsRelatedOBDDTCByStatusMask as u8,
-                status_mask,
-            ],
-        )?;
+        self.get_dtc_list(&[
+            DtcSubFunction::ReportEmissionsRelatedOBDDTCByStatusMask as u8,
+            status_mask.into(),
+        ])
+    }
+
+    /// Performs a ReadDTCInformation request which responds with the DTCStatusAvailabilityMask,
+    /// followed by a list of DTCs and their status
+    fn get_dtc_list(&mut self, args: &[u8]) -> DiagServerResult<Vec<DTC>> {
+        let resp = self.execute_command_with_response(UDSCommand::ReadDTCInformation, args)?;
+        if let Some(mask) = resp.get(2) {
+            self.dtc_status_availability_mask = Some(DtcStatusMask(*mask));
+        }
         if resp.len() < 7 {
             return Ok(vec![]); // No errors
         }
+        // Note the ECU might not support querying the DTC format, in which case return 0 as format specifier
+        let fmt = self.get_dtc_format();
+        parse_dtc_list(fmt, &resp, 3)
+    }
 
-        resp.drain(0..3);
-        if resp.len() % 4 != 0 {
-            return Err(DiagError::InvalidResponseLength); // Each DTC should be 4 bytes!
+    /// Returns the DTCStatusAvailabilityMask of the ECU, which are the status bits the ECU supports.
+    /// This is cached once it is known, as it is included in the ECU's response to most
+    /// ReadDTCInformation requests
+    pub fn get_dtc_status_availability_mask(&mut self) -> DiagServerResult<DtcStatusMask> {
+        match self.dtc_status_availability_mask {
+            Some(mask) => Ok(mask),
+            None => self
+                .get_number_of_dtcs_by_status_mask(DtcStatusMask::all())
+                .map(|r| r.0),
         }
+    }
 
-        // Now, see if we can query the ECU's DTC format
-        // Note the ECU might not support this command, in which case return 0 as format specifier
-        let fmt = match self.dtc_format {
+    /// Returns the DTC format of the ECU. If this is not yet known, then the ECU is queried for it
+    fn get_dtc_format(&mut self) -> DTCFormatType {
+        match self.dtc_format {
             Some(s) => s,
             None => self
-                .get_number_of_dtcs_by_status_mask(status_mask)
+                .get_number_of_dtcs_by_status_mask(DtcStatusMask::all())
                 .map(|r| r.1)
                 .unwrap_or(DTCFormatType::Unknown(0)),
-        };
-        let mut result: Vec<DTC> = Vec::new();
-
-        for x in (0..resp.len()).step_by(4) {
-            let dtc_code: u32 =
-                (resp[x] as u32) << 16 | (resp[x + 1] as u32) << 8 | resp[x + 2] as u32;
-            let status = resp[x + 3];
-
-            result.push(DTC {
-                format: fmt,
-                raw: dtc_code,
-                status: DTCStatus::Unknown(status), // TODO
-                mil_on: status & 0b10000000 != 0,
-                readiness_flag: false,
-            })
         }
-        Ok(result)
     }
 
+    /// Returns the snapshot record(s) (Freeze frames) stored for a DTC
     ///
+    /// ## Parameters
+    /// * dtc_mask_record - The DTC to read snapshot records of
+    /// * snapshot_record_number - The snapshot record to read (0xFF for all records)
+    /// * decoder - Optional decoder used to split and decode the DIDs within each record.
+    ///   Without a decoder, or if a DID is not known by the decoder, the DID takes the remaining
+    ///   data of the response (Including any further identifiers and records), as the length of
+    ///   its data cannot be determined.
     pub fn get_dtc_snapshot_record_by_dtc_number(
         &mut self,
         dtc_mask_record: u32,
         snapshot_record_number: u8,
-    ) -> DiagServerResult<u32> {
+        decoder: Option<&dyn DidDecoder>,
+    ) -> DiagServerResult<DtcSnapshot> {
         let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
@@ -314,29 +599,47 @@ impl UdsDiagnosticServer {
                 snapshot_record_number,
             ],
         )?;
-        Err(DiagError::NotImplemented(format!(
-            "ECU Response was: {:02X?}",
-            resp
-        )))
+        let fmt = self.get_dtc_format();
+        parse_snapshot_by_dtc_number(fmt, &resp, 2, decoder)
     }
 
     /// Returns all DTC snapshot identifications (DTC number(s) and DTCSnapshot record number(s))
-    pub fn get_dtc_snapshot_identification(&mut self) -> DiagServerResult<u32> {
-        let resp = self.execute_command_with_response(
+    pub fn get_dtc_snapshot_identification(
+        &mut self,
+    ) -> DiagServerResult<Vec<DtcSnapshotIdentification>> {
+        let mut resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[DtcSubFunction::ReportDTCSnapshotIdentifier as u8],
         )?;
-        Err(DiagError::NotImplemented(format!(
-            "ECU Response was: {:02X?}",
-            resp
-        )))
+        if resp.len() < 2 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        resp.drain(0..2);
+        if resp.len() % 4 != 0 {
+            return Err(DiagError::InvalidResponseLength); // Each identification should be 4 bytes!
+        }
+        Ok(resp
+            .chunks(4)
+            .map(|x| DtcSnapshotIdentification {
+                dtc: (x[0] as u32) << 16 | (x[1] as u32) << 8 | x[2] as u32,
+                record_number: x[3],
+            })
+            .collect())
     }
 
-    /// Returns a list of snapshot records based on the mask of snapshot_record_number (0xFF for all records)
+    /// Returns a list of snapshot records based on the mask of snapshot_record_number (0xFF for all records).
+    /// Each returned [DtcSnapshot] contains a single record.
+    ///
+    /// ## Parameters
+    /// * snapshot_record_number - The snapshot record to read (0xFF for all records)
+    /// * decoder - Optional decoder used to split and decode the DIDs within each record.
+    ///   Without a decoder, or if a DID is not known by the decoder, the DID takes the remaining
+    ///   data of the response, as the length of its data cannot be determined.
     pub fn get_dtc_snapshot_record_by_record_number(
         &mut self,
         snapshot_record_number: u8,
-    ) -> DiagServerResult<u32> {
+        decoder: Option<&dyn DidDecoder>,
+    ) -> DiagServerResult<Vec<DtcSnapshot>> {
         let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
@@ -344,23 +647,26 @@ impl UdsDiagnosticServer {
                 snapshot_record_number,
             ],
         )?;
-        Err(DiagError::NotImplemented(format!(
-            "ECU Response was: {:02X?}",
-            resp
-        )))
+        let fmt = self.get_dtc_format();
+        parse_snapshot_by_record_number(fmt, &resp, decoder)
     }
 
     /// Returns the DTCExtendedData record(s) associated with the provided DTC mask and record number.
     /// For the record_number, 0xFE implies all OBD records. and 0xFF implies all records.
     ///
-    /// ## Returns
-    /// This function will return the ECUs full response if successful
+    /// ## Parameters
+    /// * dtc - The DTC to read extended data records of
+    /// * extended_data_record_number - The record to read
+    /// * record_lengths - List of (record number, length) pairs, used to split the response
+    ///   when reading more than one record. A record which is not in this list takes the
+    ///   remaining data of the response. This can be empty when reading a single record.
     pub fn get_dtc_extended_data_record_by_dtc_number(
         &mut self,
         dtc: u32,
         extended_data_record_number: u8,
-    ) -> DiagServerResult<Vec<u8>> {
-        self.execute_command_with_response(
+        record_lengths: &[(u8, usize)],
+    ) -> DiagServerResult<DtcExtendedData> {
+        let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
                 DtcSubFunction::ReportDTCExtendedDataRecordByDTCNumber as u8,
@@ -369,20 +675,27 @@ impl UdsDiagnosticServer {
                 dtc as u8,         // Low byte
                 extended_data_record_number,
             ],
-        )
+        )?;
+        let fmt = self.get_dtc_format();
+        parse_extended_data(fmt, &resp, 2, record_lengths)
     }
 
     /// Returns a list of extended data records stored in DTC mirror memory for a given DTC.
     /// 0xFF for extended_data_record means return all extended data records.
     ///
-    /// ## Returns
-    /// This function will return the ECUs full response if successful
+    /// ## Parameters
+    /// * dtc - The DTC to read extended data records of
+    /// * extended_data_record_number - The record to read
+    /// * record_lengths - List of (record number, length) pairs, used to split the response
+    ///   when reading more than one record. A record which is not in this list takes the
+    ///   remaining data of the response. This can be empty when reading a single record.
     pub fn get_mirror_memory_dtc_extended_data_record_by_dtc_number(
         &mut self,
         dtc: u32,
         extended_data_record_number: u8,
-    ) -> DiagServerResult<Vec<u8>> {
-        self.execute_command_with_response(
+        record_lengths: &[(u8, usize)],
+    ) -> DiagServerResult<DtcExtendedData> {
+        let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
                 DtcSubFunction::ReportMirrorMemoryDTCExtendedDataRecordByDTCNumber as u8,
@@ -391,51 +704,70 @@ impl UdsDiagnosticServer {
                 dtc as u8,         // Low byte
                 extended_data_record_number,
             ],
-        )
+        )?;
+        let fmt = self.get_dtc_format();
+        parse_extended_data(fmt, &resp, 2, record_lengths)
     }
 
     /// Returns the number of DTCs stored on the ECU that match the provided severity and status mask
+    ///
+    /// ## Returns
+    /// Returns a tuple of the given information:
+    /// 1. ([DtcStatusMask]) - DTCStatusAvailabilityMask
+    /// 2. ([DTCFormatType]) - Format of the DTCs
+    /// 3. (u16) - Number of DTCs which match the severity and status mask
     pub fn get_number_of_dtcs_by_severity_mask_record(
         &mut self,
         severity_mask: u8,
-        status_mask: u8,
-    ) -> DiagServerResult<u32> {
+        status_mask: DtcStatusMask,
+    ) -> DiagServerResult<(DtcStatusMask, DTCFormatType, u16)> {
         let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
                 DtcSubFunction::ReportNumberOfDTCBySeverityMaskRecord as u8,
                 severity_mask,
-                status_mask,
+                status_mask.into(),
             ],
         )?;
-        Err(DiagError::NotImplemented(format!(
-            "ECU Response was: {:02X?}",
-            resp
-        )))
+        if resp.len() != 6 {
+            Err(DiagError::InvalidResponseLength)
+        } else {
+            self.dtc_format = Some(dtc::dtc_format_from_uds(resp[3]));
+            self.dtc_status_availability_mask = Some(DtcStatusMask(resp[2]));
+            Ok((
+                DtcStatusMask(resp[2]),
+                dtc::dtc_format_from_uds(resp[3]),
+                (resp[4] as u16) << 8 | resp[5] as u16,
+            ))
+        }
     }
 
     /// Returns a list of DTCs who's severity mask matches the provided mask
     pub fn get_dtcs_by_severity_mask_record(
         &mut self,
         severity_mask: u8,
-        status_mask: u8,
-    ) -> DiagServerResult<Vec<DTC>> {
+        status_mask: DtcStatusMask,
+    ) -> DiagServerResult<Vec<DtcSeverityRecord>> {
         let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
                 DtcSubFunction::ReportDTCBySeverityMaskRecord as u8,
                 severity_mask,
-                status_mask,
+                status_mask.into(),
             ],
         )?;
-        Err(DiagError::NotImplemented(format!(
-            "ECU Response was: {:02X?}",
-            resp
-        )))
+        let fmt = self.get_dtc_format();
+        parse_severity_records(fmt, &resp, 3, true)
     }
 
     /// Returns the severity status of a provided DTC
-    pub fn get_severity_information_of_dtc(&mut self, dtc: u32) -> DiagServerResult<u32> {
+    ///
+    /// ## Returns
+    /// The severity record of the DTC, or [None] if the ECU does not know the DTC
+    pub fn get_severity_information_of_dtc(
+        &mut self,
+        dtc: u32,
+    ) -> DiagServerResult<Option<DtcSeverityRecord>> {
         let resp = self.execute_command_with_response(
             UDSCommand::ReadDTCInformation,
             &[
@@ -445,100 +777,40 @@ impl UdsDiagnosticServer {
                 dtc as u8,
             ],
         )?;
-        Err(DiagError::NotImplemented(format!(
-            "ECU Response was: {:02X?}",
-            resp
-        )))
+        let fmt = self.get_dtc_format();
+        Ok(parse_severity_records(fmt, &resp, 3, true)?.pop())
     }
 
     /// Returns a list of all DTCs that the ECU can return
     pub fn get_supported_dtc(&mut self) -> DiagServerResult<Vec<DTC>> {
-        let mut resp = self.execute_command_with_response(
-            UDSCommand::ReadDTCInformation,
-            &[DtcSubFunction::ReportSupportedDTC as u8],
-        )?;
-        if resp.len() < 7 {
-            return Ok(vec![]); // No errors
-        }
-
-        resp.drain(0..3);
-        if resp.len() % 4 != 0 {
-            return Err(DiagError::InvalidResponseLength); // Each DTC should be 4 bytes!
-        }
-
-        // Now, see if we can query the ECU's DTC format
-        // Note the ECU might not support this command, in which case return 0 as format specifier
-        let fmt = match self.dtc_format {
-            Some(s) => s,
-            None => self
-                .get_number_of_dtcs_by_status_mask(0xFF)
-                .map(|r| r.1)
-                .unwrap_or(DTCFormatType::Unknown(0)),
-        };
-        let mut result: Vec<DTC> = Vec::new();
+        self.get_dtc_list(&[DtcSubFunction::ReportSupportedDTC as u8])
+    }
 
-        for x in (0..resp.len()).step_by(4) {
-            let dtc_code: u32 =
-                (resp[x] as u32) << 16 | (resp[x + 1] as u32) << 8 | resp[x + 2] as u32;
-            let status = resp[x + 3];
-
-            result.push(DTC {
-                format: fmt,
-                raw: dtc_code,
-                status: DTCStatus::Unknown(status), // TODO
-                mil_on: status & 0b10000000 != 0,
-                readiness_flag: false,
-            })
-        }
-        Ok(result)
+    fn get_single_dtc(&mut self, sub_function: DtcSubFunction) -> DiagServerResult<Option<DTC>> {
+        let resp = self
+            .execute_command_with_response(UDSCommand::ReadDTCInformation, &[sub_function as u8])?;
+        let fmt = self.get_dtc_format();
+        Ok(parse_dtc_list(fmt, &resp, 3)?.pop())
     }
 
     /// Returns the first failed DTC to be detected since the last DTC clear operation
     pub fn get_first_test_failed_dtc(&mut self) -> DiagServerResult<Option<DTC>> {
-        let resp = self.execute_command_with_response(
-            UDSCommand::ReadDTCInformation,
-            &[DtcSubFunction::ReportFirstTestFailedDTC as u8],
-        )?;
-        Err(DiagError::NotImplemented(format!(
-            "ECU Response was: {:02X?}",
-            resp
-        )))
+        self.get_single_dtc(DtcSubFunction::ReportFirstTestFailedDTC)
     }
 
     /// Returns the first confirmed DTC to be detected since the last DTC clear operation
     pub fn get_first_confirmed_dtc(&mut self) -> DiagServerResult<Option<DTC>> {
-        let resp = self.execute_command_with_response(
-            UDSCommand::ReadDTCInformation,
-            &[DtcSubFunction::ReportFirstConfirmedDTC as u8],
-        )?;
-        Err(DiagError::NotImplemented(format!(
-            "ECU Response was: {:02X?}",
-            resp
-        )))
+        self.get_single_dtc(DtcSubFunction::ReportFirstConfirmedDTC)
     }
 
     /// Returns the most recent DTC to be detected since the last DTC clear operation
     pub fn get_most_recent_test_failed_dtc(&mut self) -> DiagServerResult<Option<DTC>> {
-        let resp = self.execute_command_with_response(
-            UDSCommand::ReadDTCInformation,
-            &[DtcSubFunction::ReportMostRecentTestFailedDTC as u8],
-        )?;
-        Err(DiagError::NotImplemented(format!(
-            "ReportMostRecentTestFailedDTC ECU Response was: {:02X?}",
-            resp
-        )))
+        self.get_single_dtc(DtcSubFunction::ReportMostRecentTestFailedDTC)
     }
 
     /// Returns the most recent DTC to be detected since the last DTC clear operation
     pub fn get_most_recent_confirmed_dtc(&mut self) -> DiagServerResult<Option<DTC>> {
-        let resp = self.execute_command_with_response(
-            UDSCommand::ReadDTCInformation,
-            &[DtcSubFunction::ReportMostRecentConfirmedDTC as u8],
-        )?;
-        Err(DiagError::NotImplemented(format!(
-            "ReportMostRecentConfirmedDTC ECU Response was: {:02X?}",
-            resp
-        )))
+        self.get_single_dtc(DtcSubFunction::ReportMostRecentConfirmedDTC)
     }
 
     /// Returns the current number of 'pre-failed' DTCs on the ECU, which have not yet been confirmed
@@ -578,9 +850,328 @@ impl UdsDiagnosticServer {
             UDSCommand::ReadDTCInformation,
             &[DtcSubFunction::ReportDTCWithPermanentStatus as u8],
         )?;
-        Err(DiagError::NotImplemented(format!(
-            "ReportDTCWithPermanentStatus ECU Response was: {:02X?}",
-            resp
-        )))
+        let fmt = self.get_dtc_format();
+        parse_dtc_list(fmt, &resp, 3)
+    }
+
+    /// Returns the extended data record of every DTC which has the provided record stored
+    ///
+    /// ## Parameters
+    /// * extended_data_record_number - The record to read (0x00-0xEF)
+    /// * record_len - Length of the extended data record, used to split the response when
+    ///   more than one DTC has the record stored. If [None], then the response is only
+    ///   split correctly if a single DTC has the record stored.
+    pub fn get_dtc_ext_data_record_by_record_number(
+        &mut self,
+        extended_data_record_number: u8,
+        record_len: Option<usize>,
+    ) -> DiagServerResult<Vec<DtcExtendedData>> {
+        let resp = self.execute_command_with_response(
+            UDSCommand::ReadDTCInformation,
+            &[
+                DtcSubFunction::ReportDTCExtDataRecordByRecordNumber as u8,
+                extended_data_record_number,
+            ],
+        )?;
+        if resp.len() < 3 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let fmt = self.get_dtc_format();
+        let mut pos = 3;
+        let mut result = Vec::new();
+        while pos < resp.len() {
+            let dtc = parse_dtc_and_status(fmt, &resp, pos)?;
+            pos += 4;
+            let len = record_len.unwrap_or(resp.len() - pos);
+            if resp.len() < pos + len {
+                return Err(DiagError::InvalidResponseLength);
+            }
+            result.push(DtcExtendedData {
+                dtc,
+                records: vec![DtcExtendedDataRecord {
+                    record_number: resp[2],
+                    data: resp[pos..pos + len].to_vec(),
+                }],
+            });
+            pos += len;
+        }
+        Ok(result)
+    }
+
+    /// Returns a list of DTCs stored in user defined memory who's status matches the provided mask
+    ///
+    /// ## Parameters
+    /// * status_mask - DTC status mask
+    /// * memory_selection - The user defined memory to read from
+    pub fn get_user_def_memory_dtcs_by_status_mask(
+        &mut self,
+        status_mask: DtcStatusMask,
+        memory_selection: u8,
+    ) -> DiagServerResult<Vec<DTC>> {
+        let resp = self.execute_command_with_response(
+            UDSCommand::ReadDTCInformation,
+            &[
+                DtcSubFunction::ReportUserDefMemoryDTCByStatusMask as u8,
+                status_mask.into(),
+                memory_selection,
+            ],
+        )?;
+        let fmt = self.get_dtc_format();
+        // Skip memory selection and status availability mask
+        parse_dtc_list(fmt, &resp, 4)
+    }
+
+    /// Returns the snapshot record(s) stored for a DTC in user defined memory
+    ///
+    /// ## Parameters
+    /// * dtc_mask_record - The DTC to read snapshot records of
+    /// * snapshot_record_number - The snapshot record to read (0xFF for all records)
+    /// * memory_selection - The user defined memory to read from
+    /// * decoder - Optional decoder used to split and decode the DIDs within each record.
+    ///   See [UdsDiagnosticServer::get_dtc_snapshot_record_by_dtc_number]
+    pub fn get_user_def_memory_dtc_snapshot_record_by_dtc_number(
+        &mut self,
+        dtc_mask_record: u32,
+        snapshot_record_number: u8,
+        memory_selection: u8,
+        decoder: Option<&dyn DidDecoder>,
+    ) -> DiagServerResult<DtcSnapshot> {
+        let resp = self.execute_command_with_response(
+            UDSCommand::ReadDTCInformation,
+            &[
+                DtcSubFunction::ReportUserDefMemoryDTCSnapshotRecordByDTCNumber as u8,
+                (dtc_mask_record >> 16) as u8,
+                (dtc_mask_record >> 8) as u8,
+                dtc_mask_record as u8,
+                snapshot_record_number,
+                memory_selection,
+            ],
+        )?;
+        let fmt = self.get_dtc_format();
+        parse_snapshot_by_dtc_number(fmt, &resp, 3, decoder)
+    }
+
+    /// Returns the extended data record(s) stored for a DTC in user defined memory
+    ///
+    /// ## Parameters
+    /// * dtc - The DTC to read extended data records of
+    /// * extended_data_record_number - The record to read (0xFF for all records)
+    /// * memory_selection - The user defined memory to read from
+    /// * record_lengths - List of (record number, length) pairs, used to split the response.
+    ///   See [UdsDiagnosticServer::get_dtc_extended_data_record_by_dtc_number]
+    pub fn get_user_def_memory_dtc_ext_data_record_by_dtc_number(
+        &mut self,
+        dtc: u32,
+        extended_data_record_number: u8,
+        memory_selection: u8,
+        record_lengths: &[(u8, usize)],
+    ) -> DiagServerResult<DtcExtendedData> {
+        let resp = self.execute_command_with_response(
+            UDSCommand::ReadDTCInformation,
+            &[
+                DtcSubFunction::ReportUserDefMemoryDTCExtDataRecordByDTCNumber as u8,
+                (dtc >> 16) as u8,
+                (dtc >> 8) as u8,
+                dtc as u8,
+                extended_data_record_number,
+                memory_selection,
+            ],
+        )?;
+        let fmt = self.get_dtc_format();
+        parse_extended_data(fmt, &resp, 3, record_lengths)
+    }
+
+    /// Returns a list of DTCs which support the provided extended data record
+    ///
+    /// ## Parameters
+    /// * extended_data_record_number - The extended data record (0x01-0xFD)
+    pub fn get_supported_dtc_ext_data_record(
+        &mut self,
+        extended_data_record_number: u8,
+    ) -> DiagServerResult<Vec<DTC>> {
+        let resp = self.execute_command_with_response(
+            UDSCommand::ReadDTCInformation,
+            &[
+                DtcSubFunction::ReportSupportedDTCExtDataRecord as u8,
+                extended_data_record_number,
+            ],
+        )?;
+        if resp.len() < 4 {
+            // No DTCs support the record
+            return Ok(vec![]);
+        }
+        let fmt = self.get_dtc_format();
+        // Skip status availability mask and record number
+        parse_dtc_list(fmt, &resp, 4)
+    }
+
+    /// Returns a list of WWH-OBD DTCs who's status and severity match the provided masks
+    ///
+    /// ## Parameters
+    /// * functional_group_id - Functional group identifier (0x33 for emissions related systems)
+    /// * status_mask - DTC status mask
+    /// * severity_mask - DTC severity mask
+    pub fn get_wwh_obd_dtcs_by_mask_record(
+        &mut self,
+        functional_group_id: u8,
+        status_mask: DtcStatusMask,
+        severity_mask: u8,
+    ) -> DiagServerResult<Vec<DtcSeverityRecord>> {
+        let resp = self.execute_command_with_response(
+            UDSCommand::ReadDTCInformation,
+            &[
+                DtcSubFunction::ReportWWHOBDDTCByMaskRecord as u8,
+                functional_group_id,
+                status_mask.into(),
+                severity_mask,
+            ],
+        )?;
+        // Functional group, status availability mask, severity availability mask, format
+        if resp.len() < 6 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        parse_severity_records(dtc::dtc_format_from_uds(resp[5]), &resp, 6, false)
+    }
+
+    /// Returns a list of WWH-OBD DTCs that have a permanent status
+    ///
+    /// ## Parameters
+    /// * functional_group_id - Functional group identifier (0x33 for emissions related systems)
+    pub fn get_wwh_obd_dtcs_with_permanent_status(
+        &mut self,
+        functional_group_id: u8,
+    ) -> DiagServerResult<Vec<DTC>> {
+        let resp = self.execute_command_with_response(
+            UDSCommand::ReadDTCInformation,
+            &[
+                DtcSubFunction::ReportWWHOBDDTCWithPermanentStatus as u8,
+                functional_group_id,
+            ],
+        )?;
+        // Functional group, status availability mask, format
+        if resp.len() < 5 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        parse_dtc_list(dtc::dtc_format_from_uds(resp[4]), &resp, 5)
+    }
+
+    /// Returns a list of DTCs which belong to the provided readiness group
+    ///
+    /// ## Parameters
+    /// * functional_group_id - Functional group identifier (0x33 for emissions related systems)
+    /// * readiness_group_id - DTC readiness group identifier
+    pub fn get_dtcs_by_readiness_group_identifier(
+        &mut self,
+        functional_group_id: u8,
+        readiness_group_id: u8,
+    ) -> DiagServerResult<Vec<DTC>> {
+        let resp = self.execute_command_with_response(
+            UDSCommand::ReadDTCInformation,
+            &[
+                DtcSubFunction::ReportDTCInformationByDTCReadinessGroupIdentifier as u8,
+                functional_group_id,
+                readiness_group_id,
+            ],
+        )?;
+        // Functional group, status availability mask, format, readiness group
+        if resp.len() < 6 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        parse_dtc_list(dtc::dtc_format_from_uds(resp[4]), &resp, 6)
+    }
+}
+
+#[cfg(test)]
+mod read_dtc_information_test {
+    use std::collections::HashMap;
+
+    use crate::dtc::DTCFormatType;
+
+    use super::{
+        super::{DidCodec, DidDefinition, DidValue},
+        parse_extended_data, parse_severity_records, parse_snapshot_by_dtc_number, DtcSeverity,
+        WwhObdDtcClass,
+    };
+
+    #[test]
+    fn test_parse_snapshot_record() {
+        let mut decoder = HashMap::new();
+        decoder.insert(
+            0x0100,
+            DidDefinition {
+                name: "Engine speed".into(),
+                codec: DidCodec::Unsigned(2),
+            },
+        );
+        let resp = [
+            0x59, 0x04, 0x12, 0x34, 0x56, 0x2F, // DTC and status
+            0x01, 0x02, // Record 1, 2 identifiers
+            0x01, 0x00, 0x0B, 0xB8, // Engine speed
+            0x02, 0x00, 0xAA, 0xBB, // Unknown DID
+        ];
+        let snapshot =
+            parse_snapshot_by_dtc_number(DTCFormatType::Iso14229_1, &resp, 2, Some(&decoder))
+                .unwrap();
+        assert_eq!(snapshot.dtc.raw, 0x123456);
+        assert_eq!(snapshot.records.len(), 1);
+        let record = &snapshot.records[0];
+        assert_eq!(record.record_number, 1);
+        assert_eq!(record.data[0].name.as_deref(), Some("Engine speed"));
+        assert_eq!(record.data[0].value, Some(DidValue::Unsigned(3000)));
+        assert_eq!(record.data[1].did, 0x0200);
+        assert_eq!(record.data[1].data, vec![0xAA, 0xBB]);
+        assert_eq!(record.data[1].value, None);
+    }
+
+    #[test]
+    fn test_parse_snapshot_record_without_decoder() {
+        let resp = [
+            0x59, 0x04, 0x12, 0x34, 0x56, 0x2F, // DTC and status
+            0x01, 0x02, // Record 1, 2 identifiers
+            0x01, 0x00, 0x0B, 0xB8, // First DID
+            0x02, 0x00, 0xAA, // Second DID
+            0x02, 0x02, // Record 2, 2 identifiers
+            0x01, 0x00, 0x0B, 0xB9, // First DID
+            0x02, 0x00, 0xBB, // Second DID
+        ];
+        let snapshot =
+            parse_snapshot_by_dtc_number(DTCFormatType::Iso14229_1, &resp, 2, None).unwrap();
+        // Everything after the first DID is its raw data
+        assert_eq!(snapshot.records.len(), 1);
+        let record = &snapshot.records[0];
+        assert_eq!(record.number_of_identifiers, 2);
+        assert_eq!(record.data.len(), 1);
+        assert_eq!(record.data[0].did, 0x0100);
+        assert_eq!(record.data[0].data, resp[10..].to_vec());
+        assert_eq!(record.data[0].value, None);
+    }
+
+    #[test]
+    fn test_parse_extended_data() {
+        let resp = [
+            0x59, 0x06, 0x12, 0x34, 0x56, 0x2F, 0x01, 0x05, 0x02, 0x10, 0x20,
+        ];
+        let data = parse_extended_data(DTCFormatType::Iso14229_1, &resp, 2, &[(0x01, 1)]).unwrap();
+        assert_eq!(data.records.len(), 2);
+        assert_eq!(data.records[0].data, vec![0x05]);
+        assert_eq!(data.records[1].record_number, 0x02);
+        assert_eq!(data.records[1].data, vec![0x10, 0x20]);
+    }
+
+    #[test]
+    fn test_parse_severity_records() {
+        let resp = [
+            0x59, 0x08, 0xFF, 0x44, 0x10, 0x12, 0x34, 0x56,
+            0x09, // Severity, functional unit, DTC
+        ];
+        let records = parse_severity_records(DTCFormatType::Iso14229_1, &resp, 3, true).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].severity.check_at_next_halt);
+        assert_eq!(records[0].severity.class, Some(WwhObdDtcClass::ClassB1));
+        assert_eq!(records[0].functional_unit, Some(0x10));
+        assert_eq!(records[0].dtc.raw, 0x123456);
+        assert_eq!(u8::from(DtcSeverity::from(0x44)), 0x44);
+        // WWH-OBD records have no functional unit
+        assert!(parse_severity_records(DTCFormatType::Iso14229_1, &resp, 3, false).is_err());
     }
 }
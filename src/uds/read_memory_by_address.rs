@@ -0,0 +1,72 @@
+//! Provides methods for reading memory from the ECU by address with UDS
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{AddressAndLengthFormatIdentifier, UDSCommand, UdsDiagnosticServer};
+
+impl UdsDiagnosticServer {
+    /// Reads the contents of a memory region on the ECU
+    ///
+    /// ## Parameters
+    /// * alfid - Address and length format identifier, describing how many bytes
+    ///   are used to encode `address` and `size`
+    /// * address - Memory address to read from
+    /// * size - Number of bytes to read
+    ///
+    /// ## Returns
+    /// The contents of the memory region. If the ECU returns a different number of
+    /// bytes than requested, then [DiagError::InvalidResponseLength] is returned
+    pub fn read_memory_by_address(
+        &mut self,
+        alfid: AddressAndLengthFormatIdentifier,
+        address: u64,
+        size: u64,
+    ) -> DiagServerResult<Vec<u8>> {
+        let args = alfid.encode(address, size)?;
+        let res = self.execute_command_with_response(UDSCommand::ReadMemoryByAddress, &args)?;
+        if res.is_empty() || res.len() as u64 - 1 != size {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        Ok(res[1..].to_vec())
+    }
+
+    /// Reads a memory region on the ECU which is larger than a single response allows,
+    /// by splitting it into multiple [UdsDiagnosticServer::read_memory_by_address] requests.
+    ///
+    /// ## Parameters
+    /// * alfid - Address and length format identifier
+    /// * address - Start address of the memory region
+    /// * size - Number of bytes to read
+    /// * chunk_size - Maximum number of bytes to read per request
+    /// * on_progress - Called after each request with the number of bytes read so far and the total number of bytes
+    ///
+    /// ## Returns
+    /// The contents of the memory region. [DiagError::ParameterInvalid] is returned if `chunk_size` is 0,
+    /// or the memory region cannot be addressed using `alfid`
+    pub fn read_memory_region<P>(
+        &mut self,
+        alfid: AddressAndLengthFormatIdentifier,
+        address: u64,
+        size: u64,
+        chunk_size: usize,
+        mut on_progress: P,
+    ) -> DiagServerResult<Vec<u8>>
+    where
+        P: FnMut(usize, usize),
+    {
+        if chunk_size == 0 {
+            return Err(DiagError::ParameterInvalid);
+        }
+        alfid.check_region(address, size)?;
+        // The region may be larger than can be allocated up front, so the buffer grows as data is read
+        let mut res = Vec::new();
+        let mut offset = 0;
+        while offset < size {
+            let len = (chunk_size as u64).min(size - offset);
+            res.extend_from_slice(&self.read_memory_by_address(alfid, address + offset, len)?);
+            offset += len;
+            on_progress(offset as usize, size as usize);
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,222 @@
+//! Provides methods for requesting a data download (Tester to ECU) with UDS
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+/// Data format identifier of a data transfer. Describes how the transferred data is
+/// compressed and encrypted. The meaning of each method is defined by the vehicle manufacturer.
+///
+/// The default value (No compression, no encryption) is 0x00
+pub struct DataFormatIdentifier {
+    compression_method: u8,
+    encrypting_method: u8,
+}
+
+impl DataFormatIdentifier {
+    /// Creates a new data format identifier
+    ///
+    /// ## Parameters
+    /// * compression_method - Compression method (0x0-0xF). 0 means no compression
+    /// * encrypting_method - Encryption method (0x0-0xF). 0 means no encryption
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if either method is larger than 0xF
+    pub fn new(compression_method: u8, encrypting_method: u8) -> DiagServerResult<Self> {
+        if compression_method > 0x0F || encrypting_method > 0x0F {
+            return Err(DiagError::ParameterInvalid);
+        }
+        Ok(Self {
+            compression_method,
+            encrypting_method,
+        })
+    }
+
+    /// Returns the compression method
+    pub fn get_compression_method(&self) -> u8 {
+        self.compression_method
+    }
+
+    /// Returns the encrypting method
+    pub fn get_encrypting_method(&self) -> u8 {
+        self.encrypting_method
+    }
+}
+
+impl From<DataFormatIdentifier> for u8 {
+    fn from(x: DataFormatIdentifier) -> Self {
+        x.compression_method << 4 | x.encrypting_method
+    }
+}
+
+impl From<u8> for DataFormatIdentifier {
+    fn from(x: u8) -> Self {
+        Self {
+            compression_method: x >> 4,
+            encrypting_method: x & 0x0F,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Address and length format identifier. Describes how many bytes are used to
+/// encode the memory address and memory size of a request.
+pub struct AddressAndLengthFormatIdentifier {
+    address_len: u8,
+    size_len: u8,
+}
+
+impl AddressAndLengthFormatIdentifier {
+    /// Creates a new address and length format identifier
+    ///
+    /// ## Parameters
+    /// * address_len - Number of bytes used to encode the memory address (1-8)
+    /// * size_len - Number of bytes used to encode the memory size (1-8)
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if either length is not within 1-8 bytes
+    pub fn new(address_len: u8, size_len: u8) -> DiagServerResult<Self> {
+        if !(1..=8).contains(&address_len) || !(1..=8).contains(&size_len) {
+            return Err(DiagError::ParameterInvalid);
+        }
+        Ok(Self {
+            address_len,
+            size_len,
+        })
+    }
+
+    /// Returns the number of bytes used to encode the memory address
+    pub fn get_address_len(&self) -> u8 {
+        self.address_len
+    }
+
+    /// Returns the number of bytes used to encode the memory size
+    pub fn get_size_len(&self) -> u8 {
+        self.size_len
+    }
+
+    /// Encodes the format identifier, followed by the memory address and memory size
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `address` or `size` cannot fit in the
+    /// number of bytes specified by this format identifier
+    pub(crate) fn encode(&self, address: u64, size: u64) -> DiagServerResult<Vec<u8>> {
+        if !fits_in_bytes(address, self.address_len) || !fits_in_bytes(size, self.size_len) {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let mut res = Vec::with_capacity(1 + (self.address_len + self.size_len) as usize);
+        res.push(u8::from(*self));
+        res.extend_from_slice(&address.to_be_bytes()[8 - self.address_len as usize..]);
+        res.extend_from_slice(&size.to_be_bytes()[8 - self.size_len as usize..]);
+        Ok(res)
+    }
+
+    /// Checks that a memory region of `size` bytes starting at `address` can be addressed
+    /// using this format identifier
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if the end of the region overflows, or its
+    /// last address cannot fit in the number of bytes specified by this format identifier
+    pub(crate) fn check_region(&self, address: u64, size: u64) -> DiagServerResult<()> {
+        match address.checked_add(size) {
+            Some(end) if fits_in_bytes(end.saturating_sub(1).max(address), self.address_len) => {
+                Ok(())
+            }
+            _ => Err(DiagError::ParameterInvalid),
+        }
+    }
+}
+
+/// Returns true if `value` can be encoded in `len` bytes
+fn fits_in_bytes(value: u64, len: u8) -> bool {
+    len >= 8 || value >> (8 * len as u32) == 0
+}
+
+impl Default for AddressAndLengthFormatIdentifier {
+    /// 4 byte memory address and 4 byte memory size (0x44)
+    fn default() -> Self {
+        Self {
+            address_len: 4,
+            size_len: 4,
+        }
+    }
+}
+
+impl From<AddressAndLengthFormatIdentifier> for u8 {
+    fn from(x: AddressAndLengthFormatIdentifier) -> Self {
+        x.size_len << 4 | x.address_len
+    }
+}
+
+/// Parses maxNumberOfBlockLength from a positive RequestDownload or RequestUpload
+/// response (including the SID)
+pub(crate) fn parse_max_number_of_block_length(resp: &[u8]) -> DiagServerResult<u32> {
+    if resp.len() < 2 {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    let len = (resp[1] >> 4) as usize;
+    if len == 0 || len > 4 || resp.len() != 2 + len {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    Ok(resp[2..].iter().fold(0u32, |acc, x| acc << 8 | *x as u32))
+}
+
+impl UdsDiagnosticServer {
+    /// Requests the ECU to accept a data download (Tester to ECU) to a memory region.
+    /// After this, data is sent using [UdsDiagnosticServer::transfer_data], and the transfer
+    /// is finished using [UdsDiagnosticServer::request_transfer_exit].
+    ///
+    /// ## Parameters
+    /// * dfi - Data format identifier (Compression and encryption of the data)
+    /// * alfid - Address and length format identifier
+    /// * address - Memory address to download the data to
+    /// * size - Size of the (uncompressed) data to download
+    ///
+    /// ## Returns
+    /// The maxNumberOfBlockLength returned by the ECU. This is the maximum length of
+    /// each TransferData request, including the SID and block sequence counter.
+    pub fn request_download(
+        &mut self,
+        dfi: DataFormatIdentifier,
+        alfid: AddressAndLengthFormatIdentifier,
+        address: u64,
+        size: u64,
+    ) -> DiagServerResult<u32> {
+        let mut args = vec![dfi.into()];
+        args.extend_from_slice(&alfid.encode(address, size)?);
+        let res = self.execute_command_with_response(UDSCommand::RequestDownload, &args)?;
+        parse_max_number_of_block_length(&res)
+    }
+}
+
+#[cfg(test)]
+mod request_download_test {
+    use super::{parse_max_number_of_block_length, AddressAndLengthFormatIdentifier};
+
+    #[test]
+    fn test_encode_alfid() {
+        let alfid = AddressAndLengthFormatIdentifier::new(3, 2).unwrap();
+        assert_eq!(
+            alfid.encode(0x012345, 0x0400).unwrap(),
+            vec![0x23, 0x01, 0x23, 0x45, 0x04, 0x00]
+        );
+        assert!(alfid.encode(0x01234567, 0x0400).is_err());
+        assert!(AddressAndLengthFormatIdentifier::new(0, 4).is_err());
+        assert!(alfid.check_region(0xFFFF00, 0x100).is_ok());
+        assert!(alfid.check_region(0xFFFF00, 0x101).is_err());
+        assert!(AddressAndLengthFormatIdentifier::new(8, 4)
+            .unwrap()
+            .check_region(u64::MAX, 2)
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_max_block_length() {
+        assert_eq!(
+            parse_max_number_of_block_length(&[0x74, 0x20, 0x0F, 0xFF]).unwrap(),
+            0x0FFF
+        );
+        assert!(parse_max_number_of_block_length(&[0x74, 0x20, 0x0F]).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+//! Provides methods for transferring files to and from the ECU's file system with UDS
+//!
+//! Once a file transfer has been requested, the file data is transferred using TransferData,
+//! and the transfer is finished using RequestTransferExit, in the same way as
+//! RequestDownload and RequestUpload.
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{request_upload::UploadLength, DataFormatIdentifier, UDSCommand, UdsDiagnosticServer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// File transfer mode of operation
+pub enum FileTransferMode {
+    /// Adds a new file to the ECU's file system
+    AddFile,
+    /// Deletes a file from the ECU's file system
+    DeleteFile,
+    /// Replaces an existing file on the ECU's file system (Or adds it if it does not exist)
+    ReplaceFile,
+    /// Reads a file from the ECU's file system
+    ReadFile,
+    /// Reads the contents of a directory on the ECU's file system
+    ReadDir,
+    /// Resumes a previously interrupted file transfer to the ECU
+    ResumeFile,
+}
+
+impl From<FileTransferMode> for u8 {
+    fn from(x: FileTransferMode) -> Self {
+        match x {
+            FileTransferMode::AddFile => 0x01,
+            FileTransferMode::DeleteFile => 0x02,
+            FileTransferMode::ReplaceFile => 0x03,
+            FileTransferMode::ReadFile => 0x04,
+            FileTransferMode::ReadDir => 0x05,
+            FileTransferMode::ResumeFile => 0x06,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// ECU's response to a file transfer request
+pub struct FileTransferResponse {
+    /// Mode of operation echoed back by the ECU
+    pub mode: FileTransferMode,
+    /// maxNumberOfBlockLength. This is the maximum length of each TransferData request or response,
+    /// including the SID and block sequence counter. [None] for [FileTransferMode::DeleteFile]
+    pub max_block_len: Option<u32>,
+    /// Data format identifier the ECU will use. [None] for [FileTransferMode::DeleteFile]
+    pub dfi: Option<DataFormatIdentifier>,
+    /// Uncompressed size of the file ([FileTransferMode::ReadFile]),
+    /// or length of the directory information ([FileTransferMode::ReadDir])
+    pub file_size_uncompressed: Option<u64>,
+    /// Compressed size of the file. Only for [FileTransferMode::ReadFile]
+    pub file_size_compressed: Option<u64>,
+    /// Position in the file to resume the transfer from. Only for [FileTransferMode::ResumeFile]
+    pub file_position: Option<u64>,
+}
+
+/// Reads a big endian value of `len` bytes from `resp` starting at `pos`
+fn read_value(resp: &[u8], pos: &mut usize, len: usize) -> DiagServerResult<u64> {
+    if len == 0 || len > 8 || resp.len() < *pos + len {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    let value = resp[*pos..*pos + len]
+        .iter()
+        .fold(0u64, |acc, x| acc << 8 | *x as u64);
+    *pos += len;
+    Ok(value)
+}
+
+/// Parses a positive RequestFileTransfer response (including the SID)
+fn parse_file_transfer_response(
+    mode: FileTransferMode,
+    resp: &[u8],
+) -> DiagServerResult<FileTransferResponse> {
+    if resp.len() < 2 {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    if resp[1] != u8::from(mode) {
+        return Err(DiagError::MismatchedResponse(format!(
+            "Expected file transfer mode 0x{:02X}, got 0x{:02X}",
+            u8::from(mode),
+            resp[1]
+        )));
+    }
+    let mut res = FileTransferResponse {
+        mode,
+        max_block_len: None,
+        dfi: None,
+        file_size_uncompressed: None,
+        file_size_compressed: None,
+        file_position: None,
+    };
+    if mode == FileTransferMode::DeleteFile {
+        return Ok(res);
+    }
+    let mut pos = 2;
+    let block_len_len = *resp.get(pos).ok_or(DiagError::InvalidResponseLength)? as usize;
+    pos += 1;
+    if block_len_len > 4 {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    res.max_block_len = Some(read_value(resp, &mut pos, block_len_len)? as u32);
+    res.dfi = Some((*resp.get(pos).ok_or(DiagError::InvalidResponseLength)?).into());
+    pos += 1;
+    match mode {
+        FileTransferMode::ReadFile | FileTransferMode::ReadDir => {
+            let param_len = read_value(resp, &mut pos, 2)? as usize;
+            res.file_size_uncompressed = Some(read_value(resp, &mut pos, param_len)?);
+            if mode == FileTransferMode::ReadFile {
+                res.file_size_compressed = Some(read_value(resp, &mut pos, param_len)?);
+            }
+        }
+        FileTransferMode::ResumeFile => res.file_position = Some(read_value(resp, &mut pos, 8)?),
+        _ => {}
+    }
+    if pos != resp.len() {
+        return Err(DiagError::InvalidResponseLength);
+    }
+    Ok(res)
+}
+
+impl UdsDiagnosticServer {
+    /// Requests a file transfer with the ECU. For transfers with data, the data is then transferred using
+    /// [UdsDiagnosticServer::transfer_data], and the transfer is finished using [UdsDiagnosticServer::request_transfer_exit].
+    ///
+    /// ## Parameters
+    /// * mode - Mode of operation
+    /// * path - Path and name of the file or directory on the ECU
+    /// * dfi - Data format identifier (Compression and encryption of the data).
+    ///   This is ignored for [FileTransferMode::DeleteFile] and [FileTransferMode::ReadDir]
+    /// * file_size - Uncompressed and compressed size of the file to send to the ECU.
+    ///   This is only required for [FileTransferMode::AddFile], [FileTransferMode::ReplaceFile]
+    ///   and [FileTransferMode::ResumeFile]
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `path` is longer than 0xFFFF bytes,
+    /// or if `file_size` is missing when it is required
+    pub fn request_file_transfer(
+        &mut self,
+        mode: FileTransferMode,
+        path: &str,
+        dfi: DataFormatIdentifier,
+        file_size: Option<(u64, u64)>,
+    ) -> DiagServerResult<FileTransferResponse> {
+        if path.len() > 0xFFFF {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let mut args = vec![mode.into(), (path.len() >> 8) as u8, path.len() as u8];
+        args.extend_from_slice(path.as_bytes());
+        match mode {
+            FileTransferMode::AddFile
+            | FileTransferMode::ReplaceFile
+            | FileTransferMode::ResumeFile => {
+                let (uncompressed, compressed) = file_size.ok_or(DiagError::ParameterInvalid)?;
+                // Use the fewest bytes which can hold both sizes
+                let size_len =
+                    (8 - (uncompressed | compressed).leading_zeros() as usize / 8).max(1);
+                args.push(dfi.into());
+                args.push(size_len as u8);
+                args.extend_from_slice(&uncompressed.to_be_bytes()[8 - size_len..]);
+                args.extend_from_slice(&compressed.to_be_bytes()[8 - size_len..]);
+            }
+            FileTransferMode::ReadFile => args.push(dfi.into()),
+            FileTransferMode::DeleteFile | FileTransferMode::ReadDir => {}
+        }
+        let res = self.execute_command_with_response(UDSCommand::RequestFileTransfer, &args)?;
+        parse_file_transfer_response(mode, &res)
+    }
+
+    fn send_file<P>(
+        &mut self,
+        mode: FileTransferMode,
+        path: &str,
+        dfi: DataFormatIdentifier,
+        data: &[u8],
+        uncompressed_size: u64,
+        on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        P: FnMut(usize, usize),
+    {
+        let res = self.request_file_transfer(
+            mode,
+            path,
+            dfi,
+            Some((uncompressed_size, data.len() as u64)),
+        )?;
+        let start = res.file_position.unwrap_or(0) as usize;
+        if start > data.len() {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        self.transfer_data_blocks(
+            res.max_block_len.ok_or(DiagError::InvalidResponseLength)?,
+            &data[start..],
+            on_progress,
+        )?;
+        self.request_transfer_exit(&[]).map(|_| ())
+    }
+
+    /// Adds a new file to the ECU's file system. This performs the full RequestFileTransfer,
+    /// TransferData and RequestTransferExit sequence.
+    ///
+    /// ## Parameters
+    /// * path - Path and name of the file on the ECU
+    /// * dfi - Data format identifier (Compression and encryption of `data`)
+    /// * data - Contents of the file, compressed and encrypted according to `dfi`
+    /// * uncompressed_size - Size of the file once it has been decompressed by the ECU
+    /// * on_progress - Called after each block with the number of bytes sent so far and the total number of bytes
+    pub fn add_file<P>(
+        &mut self,
+        path: &str,
+        dfi: DataFormatIdentifier,
+        data: &[u8],
+        uncompressed_size: u64,
+        on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        P: FnMut(usize, usize),
+    {
+        self.send_file(
+            FileTransferMode::AddFile,
+            path,
+            dfi,
+            data,
+            uncompressed_size,
+            on_progress,
+        )
+    }
+
+    /// Replaces a file on the ECU's file system. See [UdsDiagnosticServer::add_file]
+    pub fn replace_file<P>(
+        &mut self,
+        path: &str,
+        dfi: DataFormatIdentifier,
+        data: &[u8],
+        uncompressed_size: u64,
+        on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        P: FnMut(usize, usize),
+    {
+        self.send_file(
+            FileTransferMode::ReplaceFile,
+            path,
+            dfi,
+            data,
+            uncompressed_size,
+            on_progress,
+        )
+    }
+
+    /// Resumes a previously interrupted file transfer to the ECU. The ECU responds with the position
+    /// in the file it has already received, and only the remaining part of `data` is sent.
+    /// See [UdsDiagnosticServer::add_file]
+    pub fn resume_file<P>(
+        &mut self,
+        path: &str,
+        dfi: DataFormatIdentifier,
+        data: &[u8],
+        uncompressed_size: u64,
+        on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        P: FnMut(usize, usize),
+    {
+        self.send_file(
+            FileTransferMode::ResumeFile,
+            path,
+            dfi,
+            data,
+            uncompressed_size,
+            on_progress,
+        )
+    }
+
+    /// Deletes a file from the ECU's file system
+    ///
+    /// ## Parameters
+    /// * path - Path and name of the file on the ECU
+    pub fn delete_file(&mut self, path: &str) -> DiagServerResult<()> {
+        self.request_file_transfer(
+            FileTransferMode::DeleteFile,
+            path,
+            DataFormatIdentifier::default(),
+            None,
+        )
+        .map(|_| ())
+    }
+
+    /// Reads a file from the ECU's file system. This performs the full RequestFileTransfer,
+    /// TransferData and RequestTransferExit sequence.
+    ///
+    /// ## Parameters
+    /// * path - Path and name of the file on the ECU
+    /// * dfi - Data format identifier (Compression and encryption the ECU should apply to the file)
+    /// * on_progress - Called after each block with the number of bytes received so far and the total number of bytes
+    ///
+    /// ## Returns
+    /// The contents of the file, compressed and encrypted according to `dfi`
+    pub fn read_file<P>(
+        &mut self,
+        path: &str,
+        dfi: DataFormatIdentifier,
+        on_progress: P,
+    ) -> DiagServerResult<Vec<u8>>
+    where
+        P: FnMut(usize, usize),
+    {
+        let res = self.request_file_transfer(FileTransferMode::ReadFile, path, dfi, None)?;
+        let size = res
+            .file_size_compressed
+            .ok_or(DiagError::InvalidResponseLength)? as usize;
+        self.read_file_data(size, on_progress)
+    }
+
+    /// Reads the contents of a directory on the ECU's file system. The format of the
+    /// directory information is defined by the ECU manufacturer.
+    ///
+    /// ## Parameters
+    /// * path - Path of the directory on the ECU
+    pub fn read_dir(&mut self, path: &str) -> DiagServerResult<Vec<u8>> {
+        let res = self.request_file_transfer(
+            FileTransferMode::ReadDir,
+            path,
+            DataFormatIdentifier::default(),
+            None,
+        )?;
+        let size = res
+            .file_size_uncompressed
+            .ok_or(DiagError::InvalidResponseLength)? as usize;
+        self.read_file_data(size, |_, _| {})
+    }
+
+    fn read_file_data<P>(&mut self, size: usize, on_progress: P) -> DiagServerResult<Vec<u8>>
+    where
+        P: FnMut(usize, usize),
+    {
+        // The size is reported by the ECU, so the buffer grows as data is received
+        let mut res = Vec::new();
+        self.upload_blocks(
+            UploadLength::Exact(size),
+            |block| {
+                res.extend_from_slice(block);
+                Ok(())
+            },
+            on_progress,
+        )?;
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod request_file_transfer_test {
+    use super::{parse_file_transfer_response, FileTransferMode};
+
+    #[test]
+    fn test_parse_file_transfer_response() {
+        let res = parse_file_transfer_response(
+            FileTransferMode::ReadFile,
+            &[
+                0x78, 0x04, 0x02, 0x0F, 0xFF, 0x00, 0x00, 0x02, 0x10, 0x00, 0x08, 0x00,
+            ],
+        )
+        .unwrap();
+        assert_eq!(res.max_block_len, Some(0x0FFF));
+        assert_eq!(res.file_size_uncompressed, Some(0x1000));
+        assert_eq!(res.file_size_compressed, Some(0x0800));
+
+        let res = parse_file_transfer_response(
+            FileTransferMode::ResumeFile,
+            &[
+                0x78, 0x06, 0x01, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+            ],
+        )
+        .unwrap();
+        assert_eq!(res.file_position, Some(0x100));
+
+        let res = parse_file_transfer_response(FileTransferMode::DeleteFile, &[0x78, 0x02]);
+        assert_eq!(res.unwrap().max_block_len, None);
+        assert!(parse_file_transfer_response(FileTransferMode::AddFile, &[0x78, 0x02]).is_err());
+    }
+}
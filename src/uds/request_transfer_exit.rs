@@ -0,0 +1,20 @@
+//! Provides methods for terminating a data transfer with UDS
+
+use crate::{DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
+
+impl UdsDiagnosticServer {
+    /// Terminates a data transfer which was started with [UdsDiagnosticServer::request_download]
+    ///
+    /// ## Parameters
+    /// * record - Optional transferRequestParameterRecord (For example, a checksum of the transferred data).
+    ///   The format of this is defined by the vehicle manufacturer
+    ///
+    /// ## Returns
+    /// The transferResponseParameterRecord returned by the ECU, which can be empty
+    pub fn request_transfer_exit(&mut self, record: &[u8]) -> DiagServerResult<Vec<u8>> {
+        self.execute_command_with_response(UDSCommand::RequestTransferExit, record)
+            .map(|x| x[1..].to_vec())
+    }
+}
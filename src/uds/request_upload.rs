@@ -0,0 +1,194 @@
+//! Provides methods for requesting a data upload (ECU to Tester) with UDS
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{
+    parse_max_number_of_block_length, AddressAndLengthFormatIdentifier, BlockSequenceCounter,
+    DataFormatIdentifier, UDSCommand, UDSError, UdsDiagnosticServer,
+};
+
+/// Amount of data the ECU sends during an upload
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum UploadLength {
+    /// The ECU sends exactly this many bytes
+    Exact(usize),
+    /// The ECU sends compressed data, so the number of bytes it sends is unknown. The upload ends
+    /// once the ECU sends a block shorter than `max_block_len`, or responds to the next block with
+    /// [UDSError::RequestSequenceError]
+    Compressed {
+        /// Size of the uncompressed data
+        uncompressed_size: usize,
+        /// maxNumberOfBlockLength returned by the ECU
+        max_block_len: usize,
+    },
+}
+
+impl UdsDiagnosticServer {
+    /// Requests the ECU to start a data upload (ECU to Tester) of a memory region.
+    /// After this, data is received using [UdsDiagnosticServer::transfer_data], and the transfer
+    /// is finished using [UdsDiagnosticServer::request_transfer_exit].
+    ///
+    /// ## Parameters
+    /// * dfi - Data format identifier (Compression and encryption of the data)
+    /// * alfid - Address and length format identifier
+    /// * address - Memory address to upload the data from
+    /// * size - Size of the (uncompressed) data to upload
+    ///
+    /// ## Returns
+    /// The maxNumberOfBlockLength returned by the ECU. This is the maximum length of
+    /// each TransferData response, including the SID and block sequence counter.
+    pub fn request_upload(
+        &mut self,
+        dfi: DataFormatIdentifier,
+        alfid: AddressAndLengthFormatIdentifier,
+        address: u64,
+        size: u64,
+    ) -> DiagServerResult<u32> {
+        let mut args = vec![dfi.into()];
+        args.extend_from_slice(&alfid.encode(address, size)?);
+        let res = self.execute_command_with_response(UDSCommand::RequestUpload, &args)?;
+        parse_max_number_of_block_length(&res)
+    }
+
+    /// Uploads a memory region from the ECU, passing each received block to `on_data`
+    /// as soon as it is received. This performs the full RequestUpload, TransferData and
+    /// RequestTransferExit sequence.
+    ///
+    /// If `dfi` specifies a compression method, the number of bytes the ECU sends is not known
+    /// up front. The upload then ends once the ECU sends a block shorter than its maximum block
+    /// length, or responds to the next TransferData request with [UDSError::RequestSequenceError].
+    ///
+    /// A negative response to a TransferData request aborts the upload. [UDSError::BusyRepeatRequest]
+    /// is already retried by the diagnostic server, and [UDSError::WrongBlockSequenceCounter]
+    /// cannot be recovered from, as the ECU does not report which block it expects.
+    ///
+    /// ## Parameters
+    /// * dfi - Data format identifier (Compression and encryption of the data)
+    /// * alfid - Address and length format identifier
+    /// * address - Memory address to upload the data from
+    /// * size - Number of bytes to upload
+    /// * on_data - Called with each block of data received from the ECU. If this returns an error,
+    ///   the upload is aborted and the error is returned
+    /// * on_progress - Called after each block with the number of bytes received so far and the total number of bytes.
+    ///   For compressed data, this is the number of compressed bytes received and the uncompressed size
+    pub fn upload_memory_with_callback<D, P>(
+        &mut self,
+        dfi: DataFormatIdentifier,
+        alfid: AddressAndLengthFormatIdentifier,
+        address: u64,
+        size: u64,
+        on_data: D,
+        on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        D: FnMut(&[u8]) -> DiagServerResult<()>,
+        P: FnMut(usize, usize),
+    {
+        let max_block_len = self.request_upload(dfi, alfid, address, size)? as usize;
+        let length = if dfi.get_compression_method() == 0 {
+            UploadLength::Exact(size as usize)
+        } else {
+            UploadLength::Compressed {
+                uncompressed_size: size as usize,
+                max_block_len,
+            }
+        };
+        self.upload_blocks(length, on_data, on_progress)
+    }
+
+    /// Uploads a memory region from the ECU. See [UdsDiagnosticServer::upload_memory_with_callback]
+    ///
+    /// ## Parameters
+    /// * dfi - Data format identifier (Compression and encryption of the data)
+    /// * alfid - Address and length format identifier
+    /// * address - Memory address to upload the data from
+    /// * size - Number of bytes to upload
+    /// * on_progress - Called after each block with the number of bytes received so far and the total number of bytes
+    ///
+    /// ## Returns
+    /// The contents of the memory region
+    pub fn upload_memory<P>(
+        &mut self,
+        dfi: DataFormatIdentifier,
+        alfid: AddressAndLengthFormatIdentifier,
+        address: u64,
+        size: u64,
+        on_progress: P,
+    ) -> DiagServerResult<Vec<u8>>
+    where
+        P: FnMut(usize, usize),
+    {
+        // The ECU may reject the upload, so the buffer grows as data is received
+        let mut res = Vec::new();
+        self.upload_memory_with_callback(
+            dfi,
+            alfid,
+            address,
+            size,
+            |block| {
+                res.extend_from_slice(block);
+                Ok(())
+            },
+            on_progress,
+        )?;
+        Ok(res)
+    }
+
+    /// Receives data from the ECU using TransferData requests until `length` is reached, then
+    /// finishes the transfer with RequestTransferExit. The upload must have already been requested.
+    pub(crate) fn upload_blocks<D, P>(
+        &mut self,
+        length: UploadLength,
+        mut on_data: D,
+        mut on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        D: FnMut(&[u8]) -> DiagServerResult<()>,
+        P: FnMut(usize, usize),
+    {
+        let mut received = 0;
+        let mut counter = BlockSequenceCounter::new();
+        loop {
+            let block = match length {
+                UploadLength::Exact(total) if received >= total => break,
+                UploadLength::Exact(total) => {
+                    let block = self.transfer_data(counter.get(), &[])?;
+                    if block.is_empty() || received + block.len() > total {
+                        return Err(DiagError::InvalidResponseLength);
+                    }
+                    block
+                }
+                UploadLength::Compressed { .. } => {
+                    match self.transfer_data(counter.get(), &[]) {
+                        // The ECU has no more data to send
+                        Err(DiagError::ECUError { code, .. })
+                            if received > 0
+                                && UDSError::from(code) == UDSError::RequestSequenceError =>
+                        {
+                            break
+                        }
+                        res => res?,
+                    }
+                }
+            };
+            on_data(&block)?;
+            counter.advance();
+            received += block.len();
+            match length {
+                UploadLength::Exact(total) => on_progress(received, total),
+                UploadLength::Compressed {
+                    uncompressed_size,
+                    max_block_len,
+                } => {
+                    on_progress(received, uncompressed_size);
+                    // A block shorter than the maximum (Excluding the SID and block sequence counter)
+                    // is the last one
+                    if block.len() + 2 < max_block_len {
+                        break;
+                    }
+                }
+            }
+        }
+        self.request_transfer_exit(&[]).map(|_| ())
+    }
+}
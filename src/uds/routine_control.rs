@@ -0,0 +1,244 @@
+//! Routine management wrapper for UDS
+
+use std::time::{Duration, Instant};
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// UDS Routine identifier
+pub enum RoutineIdentifier {
+    /// Erase memory routine (0xFF00). The entry options are the memory region to erase,
+    /// and their format is defined by the vehicle manufacturer
+    EraseMemory,
+    /// Check programming dependencies routine (0xFF01). Used after programming the ECU
+    /// to verify that all software and data blocks are valid and compatible
+    CheckProgrammingDependencies,
+    /// Erase mirror memory DTCs routine (0xFF02)
+    EraseMirrorMemoryDTCs,
+    /// Any other routine identifier
+    Other(u16),
+}
+
+impl From<RoutineIdentifier> for u16 {
+    fn from(x: RoutineIdentifier) -> Self {
+        match x {
+            RoutineIdentifier::EraseMemory => 0xFF00,
+            RoutineIdentifier::CheckProgrammingDependencies => 0xFF01,
+            RoutineIdentifier::EraseMirrorMemoryDTCs => 0xFF02,
+            RoutineIdentifier::Other(x) => x,
+        }
+    }
+}
+
+impl From<u16> for RoutineIdentifier {
+    fn from(x: u16) -> Self {
+        match x {
+            0xFF00 => RoutineIdentifier::EraseMemory,
+            0xFF01 => RoutineIdentifier::CheckProgrammingDependencies,
+            0xFF02 => RoutineIdentifier::EraseMirrorMemoryDTCs,
+            _ => RoutineIdentifier::Other(x),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Routine control sub function
+pub enum RoutineControlType {
+    /// Start the routine
+    StartRoutine,
+    /// Stop the routine
+    StopRoutine,
+    /// Request the results of the routine
+    RequestRoutineResults,
+}
+
+impl From<RoutineControlType> for u8 {
+    fn from(x: RoutineControlType) -> Self {
+        match x {
+            RoutineControlType::StartRoutine => 0x01,
+            RoutineControlType::StopRoutine => 0x02,
+            RoutineControlType::RequestRoutineResults => 0x03,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Positive response from the ECU to a routine control request
+pub struct RoutineControlResponse {
+    /// Routine information byte. The meaning of this is defined by the vehicle manufacturer,
+    /// but it is commonly used to report the state of the routine (Running, completed, failed).
+    /// This is [None] if the ECU did not return any data after the routine identifier
+    pub routine_info: Option<u8>,
+    /// Routine status record following the routine information byte.
+    /// This can be empty if the routine does not return any data
+    pub status_record: Vec<u8>,
+}
+
+/// Parses a positive routine control response (including the SID)
+pub(crate) fn parse_routine_control_response(
+    control_type: RoutineControlType,
+    rid: u16,
+    resp: &[u8],
+) -> DiagServerResult<RoutineControlResponse> {
+    if resp.len() < 4 {
+        // Require Positive SID, routine control type, RID << 8, RID & 0xFF
+        return Err(DiagError::InvalidResponseLength);
+    }
+    let control_type_response = resp[1] & 0x7F;
+    let rid_response = (resp[2] as u16) << 8 | resp[3] as u16;
+    if control_type_response != u8::from(control_type) || rid_response != rid {
+        return Err(DiagError::MismatchedResponse(format!(
+            "Expected routine 0x{:04X} (Type 0x{:02X}), got routine 0x{:04X} (Type 0x{:02X})",
+            rid,
+            u8::from(control_type),
+            rid_response,
+            control_type_response
+        )));
+    }
+    Ok(RoutineControlResponse {
+        routine_info: resp.get(4).copied(),
+        status_record: resp.get(5..).map(|x| x.to_vec()).unwrap_or_default(),
+    })
+}
+
+#[derive(Debug)]
+/// UDS Routine execution wrapper
+pub struct UdsRoutineManager<'a> {
+    server: &'a mut UdsDiagnosticServer,
+    r_id: RoutineIdentifier,
+}
+
+impl<'a> UdsRoutineManager<'a> {
+    /// Creates a new routine manager.
+    ///
+    /// NOTE: Unlike [crate::kwp2000::KwpRoutineManager], this does NOT change the diagnostic
+    /// session of the ECU, as the session each routine requires differs between routines
+    /// (For example, erase memory typically requires programming session).
+    ///
+    /// ## Parameters
+    /// * rid - The routine identifier
+    /// * server - Reference to running UDS diagnostic server
+    pub fn new(rid: RoutineIdentifier, server: &'a mut UdsDiagnosticServer) -> Self {
+        Self { server, r_id: rid }
+    }
+
+    /// Returns the routine identifier this manager controls
+    pub fn get_routine_identifier(&self) -> RoutineIdentifier {
+        self.r_id
+    }
+
+    fn execute_routine_control(
+        &mut self,
+        control_type: RoutineControlType,
+        options: &[u8],
+    ) -> DiagServerResult<RoutineControlResponse> {
+        let rid: u16 = self.r_id.into();
+        let mut args = Vec::with_capacity(options.len() + 3);
+        args.push(control_type.into());
+        args.push((rid >> 8) as u8);
+        args.push(rid as u8);
+        args.extend_from_slice(options);
+        let res = self
+            .server
+            .execute_command_with_response(UDSCommand::RoutineControl, &args)?;
+        parse_routine_control_response(control_type, rid, &res)
+    }
+
+    /// Attempts to start the routine
+    ///
+    /// ## Parameters
+    /// * entry_options - Optional routine control option record to send with the request
+    pub fn start_routine(
+        &mut self,
+        entry_options: &[u8],
+    ) -> DiagServerResult<RoutineControlResponse> {
+        self.execute_routine_control(RoutineControlType::StartRoutine, entry_options)
+    }
+
+    /// Attempts to stop the routine. Note that some routines automatically exit themselves
+    /// and do NOT need to be manually stopped
+    ///
+    /// ## Parameters
+    /// * exit_options - Optional routine control option record to send with the request
+    pub fn stop_routine(
+        &mut self,
+        exit_options: &[u8],
+    ) -> DiagServerResult<RoutineControlResponse> {
+        self.execute_routine_control(RoutineControlType::StopRoutine, exit_options)
+    }
+
+    /// Requests the results of the routine
+    pub fn request_routine_results(&mut self) -> DiagServerResult<RoutineControlResponse> {
+        self.execute_routine_control(RoutineControlType::RequestRoutineResults, &[])
+    }
+
+    /// Repeatedly requests the results of the routine until the ECU reports that the
+    /// routine has completed.
+    ///
+    /// ## Parameters
+    /// * poll_interval - Time to wait between each request for the routine results
+    /// * timeout - Maximum amount of time to wait for the routine to complete
+    /// * is_complete - Function which checks the routine results, and returns true if the
+    ///   routine has completed. As the format of the routine results is vehicle manufacturer specific,
+    ///   this must be provided by the caller.
+    ///
+    /// ## Returns
+    /// The routine results which `is_complete` accepted. If the routine does not complete
+    /// within `timeout`, then [DiagError::Timeout] is returned
+    pub fn wait_for_completion<F>(
+        &mut self,
+        poll_interval: Duration,
+        timeout: Duration,
+        mut is_complete: F,
+    ) -> DiagServerResult<RoutineControlResponse>
+    where
+        F: FnMut(&RoutineControlResponse) -> bool,
+    {
+        let start = Instant::now();
+        loop {
+            let res = self.request_routine_results()?;
+            if is_complete(&res) {
+                return Ok(res);
+            }
+            if start.elapsed() + poll_interval > timeout {
+                return Err(DiagError::Timeout);
+            }
+            std::thread::sleep(poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod routine_control_test {
+    use super::{parse_routine_control_response, RoutineControlType};
+
+    #[test]
+    fn test_parse_routine_response() {
+        let res = parse_routine_control_response(
+            RoutineControlType::StartRoutine,
+            0xFF00,
+            &[0x71, 0x01, 0xFF, 0x00, 0x02, 0xAA, 0xBB],
+        )
+        .unwrap();
+        assert_eq!(res.routine_info, Some(0x02));
+        assert_eq!(res.status_record, vec![0xAA, 0xBB]);
+
+        let res = parse_routine_control_response(
+            RoutineControlType::RequestRoutineResults,
+            0xFF01,
+            &[0x71, 0x03, 0xFF, 0x01],
+        )
+        .unwrap();
+        assert_eq!(res.routine_info, None);
+        assert!(res.status_record.is_empty());
+
+        assert!(parse_routine_control_response(
+            RoutineControlType::StopRoutine,
+            0xFF00,
+            &[0x71, 0x02, 0xFF, 0x01]
+        )
+        .is_err());
+    }
+}
@@ -1,4 +1,8 @@
-///! Functions and data for ReadScalingDataById UDS Service
+//! Functions and data for ReadScalingDataById UDS Service
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 /// Scaling data byte extensions
@@ -43,7 +47,7 @@ pub enum ScalingByteExtension {
     Volt,
     /// Coulomb - Measure of electrical charge. Postfix: `C`
     Coulomb,
-    /// Ohm - Measure of electrical resistance. Postfix: `W`
+    /// Ohm - Measure of electrical resistance. Postfix: `Ω`
     Ohm,
     /// Farad - Measure of electrical capacitance. Postfix: `F`
     Farad,
@@ -159,7 +163,7 @@ pub enum ScalingByteExtension {
     Centi,
     /// Milli prefix - Prefix `m`
     Milli,
-    /// micro prefix - Prefix `m`
+    /// micro prefix - Prefix `µ`
     Micro,
     /// Nano prefix - Prefix `n`
     Nano,
@@ -233,7 +237,7 @@ pub enum ScalingByteHigh {
 
 impl From<u8> for ScalingByteHigh {
     fn from(x: u8) -> Self {
-        match x & 0xF0 {
+        match x >> 4 {
             0x00 => Self::UnsignedNumeric {
                 num_bytes: x & 0x0F,
             },
@@ -281,7 +285,7 @@ impl ScalingByteExtension {
             ScalingByteExtension::Ampere => Some("A"),
             ScalingByteExtension::Volt => Some("V"),
             ScalingByteExtension::Coulomb => Some("C"),
-            ScalingByteExtension::Ohm => Some("W"),
+            ScalingByteExtension::Ohm => Some("Ω"),
             ScalingByteExtension::Farad => Some("F"),
             ScalingByteExtension::Henry => Some("H"),
             ScalingByteExtension::Siemens => Some("S"),
@@ -337,13 +341,13 @@ impl ScalingByteExtension {
             ScalingByteExtension::Tera => Some("T"),
             ScalingByteExtension::Giga => Some("G"),
             ScalingByteExtension::Mega => Some("M"),
-            ScalingByteExtension::Kilo => Some("h"),
+            ScalingByteExtension::Kilo => Some("k"),
             ScalingByteExtension::Hecto => Some("h"),
             ScalingByteExtension::Deca => Some("da"),
             ScalingByteExtension::Deci => Some("d"),
             ScalingByteExtension::Centi => Some("c"),
             ScalingByteExtension::Milli => Some("m"),
-            ScalingByteExtension::Micro => Some("m"),
+            ScalingByteExtension::Micro => Some("µ"),
             ScalingByteExtension::Nano => Some("n"),
             ScalingByteExtension::Pico => Some("p"),
             ScalingByteExtension::Femto => Some("f"),
@@ -396,7 +400,7 @@ impl From<u8> for ScalingByteExtension {
             0x24 => Self::Pascal,
             0x25 => Self::Bar,
             0x26 => Self::Atmosphere,
-            0x27 => Self::PoundForce,
+            0x27 => Self::Psi,
             0x28 => Self::Becquerel,
             0x29 => Self::Lumen,
             0x2A => Self::Lux,
@@ -456,55 +460,160 @@ impl From<u8> for ScalingByteExtension {
     }
 }
 
+/// Decodes a 2 byte constant of a scaling formula. The upper 4 bits are a signed exponent,
+/// and the lower 12 bits are a signed mantissa (Value = mantissa * 10^exponent)
+fn decode_formula_constant(b0: u8, b1: u8) -> f32 {
+    let exponent = ((b0 as i8) >> 4) as i32;
+    let mantissa = (((b0 as i16) << 12 | (b1 as i16) << 4) >> 4) as f32;
+    mantissa * 10f32.powi(exponent)
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// A scaled value of a DID
+pub struct ScaledValue {
+    /// The value after applying the scaling formula
+    pub value: f32,
+    /// The unit of the value (Including its prefix). This is empty if the value has no unit
+    pub unit: String,
+}
+
 /// Represents Scaling data structure returned from ECU
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ScalingData {
-    x: f32,
+    data_type: Option<ScalingByteHigh>,
     c0: f32,
     c1: f32,
     c2: f32,
-    mapping_byte: u8,
+    mapping_byte: Option<u8>,
     byte_ext: Vec<ScalingByteExtension>,
 }
 
 impl ScalingData {
     /// Creates a new scaling data structure
     pub(crate) fn new(
-        x: i32,
-        c0: i32,
-        c1: i32,
-        c2: i32,
-        mapping_byte: u8,
+        data_type: Option<ScalingByteHigh>,
+        mapping_byte: Option<u8>,
+        c0: f32,
+        c1: f32,
+        c2: f32,
         byte_ext: &[ScalingByteExtension],
     ) -> Self {
         Self {
-            x: x as f32,
-            c0: c0 as f32,
-            c1: c1 as f32,
-            c2: c2 as f32,
+            data_type,
+            c0,
+            c1,
+            c2,
             mapping_byte,
             byte_ext: byte_ext.to_vec(),
         }
     }
 
+    /// Parses the scaling data record of a positive ReadScalingDataByIdentifier response
+    /// (Everything after the DID)
+    pub(crate) fn from_scaling_record(record: &[u8]) -> DiagServerResult<Self> {
+        if record.is_empty() {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let mut data_type = None;
+        let mut mapping_byte = None;
+        let mut constants = [0f32; 3];
+        let mut byte_ext = Vec::new();
+        let mut pos = 0;
+        while pos < record.len() {
+            let scaling_byte = record[pos];
+            let len = (scaling_byte & 0x0F) as usize;
+            pos += 1;
+            match ScalingByteHigh::from(scaling_byte) {
+                ScalingByteHigh::Formula => {
+                    // Formula identifier, followed by 2 bytes per constant
+                    if !matches!(len, 1 | 3 | 5 | 7) || record.len() < pos + len {
+                        return Err(DiagError::InvalidResponseLength);
+                    }
+                    mapping_byte = Some(record[pos]);
+                    for (idx, c) in record[pos + 1..pos + len].chunks(2).enumerate() {
+                        constants[idx] = decode_formula_constant(c[0], c[1]);
+                    }
+                    pos += len;
+                }
+                ScalingByteHigh::UnitOrFormat => {
+                    if record.len() < pos + len {
+                        return Err(DiagError::InvalidResponseLength);
+                    }
+                    byte_ext.extend(
+                        record[pos..pos + len]
+                            .iter()
+                            .map(|x| ScalingByteExtension::from(*x)),
+                    );
+                    pos += len;
+                }
+                x @ (ScalingByteHigh::BitMappingWithMask
+                | ScalingByteHigh::StateAndConnectionType) => {
+                    // Followed by the validity mask, or the state and connection type byte
+                    if record.len() < pos + len {
+                        return Err(DiagError::InvalidResponseLength);
+                    }
+                    data_type.get_or_insert(x);
+                    pos += len;
+                }
+                x => {
+                    data_type.get_or_insert(x);
+                }
+            }
+        }
+        Ok(Self::new(
+            data_type,
+            mapping_byte,
+            constants[0],
+            constants[1],
+            constants[2],
+            &byte_ext,
+        ))
+    }
+
+    /// Returns the data type of the DID's data record. This is [None] if the ECU
+    /// only reported a formula or unit
+    pub fn get_data_type(&self) -> Option<ScalingByteHigh> {
+        self.data_type
+    }
+
     /// Returns the list of scaling data presentation of the scaling data.
     /// Note that there can be more than one! (EG: Having a prefix and postfix scaling byte)
     pub fn get_scaling_byte(&self) -> &[ScalingByteExtension] {
         &self.byte_ext
     }
 
+    /// Returns the unit of the scaling data, made up of the prefix and postfix of
+    /// each scaling byte. This is empty if the data has no unit
+    pub fn get_unit(&self) -> String {
+        let prefix: String = self
+            .byte_ext
+            .iter()
+            .filter_map(|x| x.get_prefix())
+            .collect();
+        let postfix: String = self
+            .byte_ext
+            .iter()
+            .filter_map(|x| x.get_postfix())
+            .collect();
+        format!("{}{}", prefix, postfix)
+    }
+
     /// Returns a converted value from raw.
     /// If the conversion formula falls under VMS (Vehicle manufacture specific), then None is returned.
-    pub fn get_mapping_from_raw(&self) -> Option<f32> {
+    /// If the scaling data has no formula, then `x` is returned unchanged.
+    pub fn get_mapping_from_raw(&self, x: f32) -> Option<f32> {
         let c0 = self.c0;
         let c1 = self.c1;
         let c2 = self.c2;
-        let x = self.x;
-        match self.mapping_byte {
+        let formula = match self.mapping_byte {
+            Some(formula) => formula,
+            None => return Some(x),
+        };
+        match formula {
             0x00 => Some(c0 * x + c1),
             0x01 => Some(c0 * (x + c1)),
             0x02 => Some(c0 / (x + c1) + c2),
-            0x03 => Some(x / (c0 + c1)),
+            0x03 => Some(x / c0 + c1),
             0x04 => Some((x + c0) / c1),
             0x05 => Some((x + c0) / c1 + c2),
             0x06 => Some(c0 * x),
@@ -514,4 +623,117 @@ impl ScalingData {
             _ => None, // VMS or reserved
         }
     }
+
+    /// Applies the scaling data to the raw data record of a DID
+    ///
+    /// ## Parameters
+    /// * raw - Raw data record of the DID
+    ///
+    /// ## Returns
+    /// The scaled value with its unit. [DiagError::ParameterInvalid] is returned if the
+    /// data type is not numeric, or the conversion formula is vehicle manufacturer specific.
+    /// [DiagError::InvalidResponseLength] is returned if `raw` does not match the length of the data type.
+    pub fn apply(&self, raw: &[u8]) -> DiagServerResult<ScaledValue> {
+        let x = match self.data_type {
+            Some(ScalingByteHigh::UnsignedNumeric { num_bytes })
+            | Some(ScalingByteHigh::SignedNumeric { num_bytes }) => {
+                if raw.len() != num_bytes as usize || raw.is_empty() || raw.len() > 4 {
+                    return Err(DiagError::InvalidResponseLength);
+                }
+                let unsigned = raw.iter().fold(0u32, |acc, x| acc << 8 | *x as u32);
+                if let Some(ScalingByteHigh::SignedNumeric { .. }) = self.data_type {
+                    // Sign extend from the top bit of the value
+                    let shift = 32 - 8 * raw.len() as u32;
+                    (((unsigned << shift) as i32) >> shift) as f32
+                } else {
+                    unsigned as f32
+                }
+            }
+            Some(ScalingByteHigh::SignedFloatingPoint) => {
+                if raw.len() != 4 {
+                    return Err(DiagError::InvalidResponseLength);
+                }
+                f32::from_be_bytes([raw[0], raw[1], raw[2], raw[3]])
+            }
+            Some(ScalingByteHigh::BCD) => {
+                let mut res = 0f32;
+                for digit in raw.iter().flat_map(|x| [x >> 4, x & 0x0F]) {
+                    if digit > 9 {
+                        return Err(DiagError::ParameterInvalid);
+                    }
+                    res = res * 10.0 + digit as f32;
+                }
+                res
+            }
+            _ => return Err(DiagError::ParameterInvalid),
+        };
+        Ok(ScaledValue {
+            value: self
+                .get_mapping_from_raw(x)
+                .ok_or(DiagError::ParameterInvalid)?,
+            unit: self.get_unit(),
+        })
+    }
+}
+
+impl UdsDiagnosticServer {
+    /// Reads the scaling data of a DID from the ECU. This describes the data type, conversion
+    /// formula and unit of the DID's data record, which can then be applied to the data record
+    /// using [ScalingData::apply]
+    ///
+    /// ## Parameters
+    /// * did - The 16 bit data identifier
+    pub fn read_scaling_data_by_identifier(&mut self, did: u16) -> DiagServerResult<ScalingData> {
+        let res = self.execute_command_with_response(
+            UDSCommand::ReadScalingDataByIdentifier,
+            &[(did >> 8) as u8, did as u8],
+        )?;
+        if res.len() < 3 {
+            // Require Positive SID, DID << 8, DID & 0xFF
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let did_response = (res[1] as u16) << 8 | res[2] as u16;
+        if did_response != did {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected identifier 0x{:04X}, got identifier 0x{:04X}",
+                did, did_response
+            )));
+        }
+        ScalingData::from_scaling_record(&res[3..])
+    }
+}
+
+#[cfg(test)]
+mod scaling_data_test {
+    use super::{ScalingByteHigh, ScalingData};
+
+    #[test]
+    fn test_parse_and_apply_scaling_data() {
+        // 2 byte unsigned, formula 0x00 with C0 = 0.1 (0xF001) and C1 = -40 (0x0FD8), unit Kilo + Meter
+        let scaling = ScalingData::from_scaling_record(&[
+            0x02, 0x95, 0x00, 0xF0, 0x01, 0x0F, 0xD8, 0xA2, 0x45, 0x01,
+        ])
+        .unwrap();
+        assert_eq!(
+            scaling.get_data_type(),
+            Some(ScalingByteHigh::UnsignedNumeric { num_bytes: 2 })
+        );
+        assert_eq!(scaling.get_unit(), "km");
+        let value = scaling.apply(&[0x01, 0xF4]).unwrap();
+        assert!((value.value - 10.0).abs() < 0.001);
+        assert_eq!(value.unit, "km");
+        assert!(scaling.apply(&[0x01]).is_err());
+    }
+
+    #[test]
+    fn test_parse_state_and_connection_type() {
+        // State and connection type byte 0x12, unit Meter
+        let scaling = ScalingData::from_scaling_record(&[0xB1, 0x12, 0xA1, 0x01]).unwrap();
+        assert_eq!(
+            scaling.get_data_type(),
+            Some(ScalingByteHigh::StateAndConnectionType)
+        );
+        assert_eq!(scaling.get_unit(), "m");
+        assert!(ScalingData::from_scaling_record(&[0xB1]).is_err());
+    }
 }
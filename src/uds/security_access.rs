@@ -1,10 +1,41 @@
 //! Provides methods for security seed/key access to the ECU in order to unlock functions which
 //! are considered secure such as writing or reading to specific memory regions on the ECU
 //!
-//! Currently, only default seed/key (0x01/0x02) are supported
+//! Any security level can be used. Each level is made up of an odd request seed sub function,
+//! and an even send key sub function (level + 1).
 
-use super::{UDSCommand, UdsDiagnosticServer};
-use crate::{DiagServerResult, DiagnosticServer};
+use std::time::Duration;
+
+use log::warn;
+
+use super::{UDSCommand, UDSError, UdsDiagnosticServer};
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+/// Default time to wait before requesting a seed again if the ECU responds with
+/// [UDSError::RequiredTimeDelayNotExpired]
+pub(crate) const DEFAULT_SECURITY_ACCESS_DELAY: Duration = Duration::from_secs(10);
+
+/// Algorithm for calculating a security key from a seed provided by the ECU.
+///
+/// This is implemented for any function or closure with the signature
+/// `Fn(u8, &[u8]) -> DiagServerResult<Vec<u8>>`
+pub trait SecurityKeyAlgorithm {
+    /// Calculates the key for a seed
+    ///
+    /// ## Parameters
+    /// * level - The security level (Request seed sub function) the seed was requested with
+    /// * seed - The seed provided by the ECU
+    fn calculate_key(&self, level: u8, seed: &[u8]) -> DiagServerResult<Vec<u8>>;
+}
+
+impl<F> SecurityKeyAlgorithm for F
+where
+    F: Fn(u8, &[u8]) -> DiagServerResult<Vec<u8>>,
+{
+    fn calculate_key(&self, level: u8, seed: &[u8]) -> DiagServerResult<Vec<u8>> {
+        self(level, seed)
+    }
+}
 
 /// Security operation request
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -24,8 +55,16 @@ impl From<SecurityOperation> for u8 {
     }
 }
 
+/// Checks if a security level is a valid request seed sub function
+fn check_security_level(level: u8) -> DiagServerResult<()> {
+    if level & 0x01 == 0 || level > 0x7D {
+        return Err(DiagError::ParameterInvalid);
+    }
+    Ok(())
+}
+
 impl UdsDiagnosticServer {
-    /// Requests a seed from the ECU for security access.
+    /// Requests a seed from the ECU for security access using the default security level (0x01).
     ///
     /// Once the key is calculated from the response seed, run [UdsDiagnosticServer::send_key] to send the computed key to the ECU
     ///
@@ -35,15 +74,10 @@ impl UdsDiagnosticServer {
     /// ## Returns
     /// Returns the security key's seed
     pub fn request_seed(&mut self) -> DiagServerResult<Vec<u8>> {
-        let mut resp = self.execute_command_with_response(
-            UDSCommand::SecurityAccess,
-            &[SecurityOperation::RequestSeed.into()],
-        )?;
-        resp.drain(0..2); // Remove SID and PID, so just seed value left
-        Ok(resp)
+        self.request_seed_for_level(SecurityOperation::RequestSeed.into())
     }
 
-    /// Sends the computed key to the ECU.
+    /// Sends the computed key to the ECU using the default security level (0x01).
     ///
     /// If this function is successful, then the ECU has now allows access to security protected memory regions and functions
     ///
@@ -51,10 +85,99 @@ impl UdsDiagnosticServer {
     /// * server - The UDS Diagnostic server
     /// * key - The computed key to send to the ECU
     pub fn send_key(&mut self, key: &[u8]) -> DiagServerResult<()> {
+        self.send_key_for_level(SecurityOperation::RequestSeed.into(), key)
+    }
+
+    /// Requests a seed from the ECU for a specific security level.
+    ///
+    /// Once the key is calculated from the response seed, run [UdsDiagnosticServer::send_key_for_level]
+    /// with the same level to send the computed key to the ECU
+    ///
+    /// ## Parameters
+    /// * level - The security level. This is the request seed sub function, and must be an odd number between 0x01-0x7D
+    ///
+    /// ## Returns
+    /// Returns the security key's seed. [DiagError::ParameterInvalid] is returned if `level` is invalid
+    pub fn request_seed_for_level(&mut self, level: u8) -> DiagServerResult<Vec<u8>> {
+        check_security_level(level)?;
+        let mut resp = self.execute_command_with_response(UDSCommand::SecurityAccess, &[level])?;
+        if resp.len() < 2 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if resp[1] != level {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected security level 0x{:02X}, got 0x{:02X}",
+                level, resp[1]
+            )));
+        }
+        resp.drain(0..2); // Remove SID and PID, so just seed value left
+        Ok(resp)
+    }
+
+    /// Sends the computed key to the ECU for a specific security level.
+    ///
+    /// ## Parameters
+    /// * level - The security level. This must be the same level which was provided to
+    ///   [UdsDiagnosticServer::request_seed_for_level]. The send key sub function (level + 1) is derived from it
+    /// * key - The computed key to send to the ECU
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `level` is invalid
+    pub fn send_key_for_level(&mut self, level: u8, key: &[u8]) -> DiagServerResult<()> {
+        check_security_level(level)?;
         let mut payload = Vec::with_capacity(key.len() + 1);
-        payload.push(SecurityOperation::SendKey.into());
+        payload.push(level + 1);
         payload.extend_from_slice(key);
         self.execute_command_with_response(UDSCommand::SecurityAccess, &payload)
             .map(|_| ())
     }
+
+    /// Sets the time [UdsDiagnosticServer::unlock] waits before requesting a seed again if the ECU
+    /// responds with [UDSError::RequiredTimeDelayNotExpired]. The default is 10 seconds
+    pub fn set_security_access_delay(&mut self, delay: Duration) {
+        self.security_access_delay = delay
+    }
+
+    /// Unlocks a security level on the ECU by requesting a seed, calculating the key using `algo`,
+    /// and then sending the key to the ECU.
+    ///
+    /// * If the ECU responds with [UDSError::RequiredTimeDelayNotExpired] when requesting the seed,
+    ///   then this function waits for the security access delay (See [UdsDiagnosticServer::set_security_access_delay])
+    ///   and requests the seed once more.
+    /// * If the ECU responds with [UDSError::ExceedNumberOfAttempts] or [UDSError::InvalidKey],
+    ///   then the error is returned without retrying, as retrying would only increase the ECU's lockout time.
+    /// * If the ECU responds with a seed of all zeros, then the level is already unlocked and no key is sent.
+    /// * If the ECU responds without a seed, then [DiagError::InvalidResponseLength] is returned.
+    ///
+    /// ## Parameters
+    /// * level - The security level. This is the request seed sub function, and must be an odd number between 0x01-0x7D
+    /// * algo - The algorithm to calculate the key from the seed
+    pub fn unlock<A: SecurityKeyAlgorithm + ?Sized>(
+        &mut self,
+        level: u8,
+        algo: &A,
+    ) -> DiagServerResult<()> {
+        let seed = match self.request_seed_for_level(level) {
+            Err(DiagError::ECUError { code, .. })
+                if UDSError::from(code) == UDSError::RequiredTimeDelayNotExpired =>
+            {
+                warn!(
+                    "Security access delay has not expired. Waiting {:?} before requesting seed again",
+                    self.security_access_delay
+                );
+                std::thread::sleep(self.security_access_delay);
+                self.request_seed_for_level(level)?
+            }
+            res => res?,
+        };
+        if seed.is_empty() {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if seed.iter().all(|x| *x == 0x00) {
+            // Already unlocked
+            return Ok(());
+        }
+        let key = algo.calculate_key(level, &seed)?;
+        self.send_key_for_level(level, &key)
+    }
 }
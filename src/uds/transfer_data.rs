@@ -0,0 +1,119 @@
+//! Provides methods for transferring data blocks to and from the ECU with UDS
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Block sequence counter used by TransferData.
+///
+/// The counter starts at 0x01 for the first block of a transfer, and wraps
+/// around from 0xFF to 0x00.
+pub struct BlockSequenceCounter(u8);
+
+impl BlockSequenceCounter {
+    /// Creates a new block sequence counter for a new transfer, starting at 0x01
+    pub fn new() -> Self {
+        Self(0x01)
+    }
+
+    /// Returns the current value of the counter
+    pub fn get(&self) -> u8 {
+        self.0
+    }
+
+    /// Advances the counter to the next block, wrapping around from 0xFF to 0x00
+    pub fn advance(&mut self) {
+        self.0 = self.0.wrapping_add(1)
+    }
+}
+
+impl Default for BlockSequenceCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UdsDiagnosticServer {
+    /// Transfers a single block of data to or from the ECU
+    ///
+    /// ## Parameters
+    /// * block_sequence_counter - Block sequence counter of this block. See [BlockSequenceCounter]
+    /// * data - The transferRequestParameterRecord. For a download, this is the block of data to send.
+    ///   For an upload, this is usually empty.
+    ///
+    /// ## Returns
+    /// The transferResponseParameterRecord returned by the ECU. If the ECU's response does not
+    /// echo back the same block sequence counter, then [DiagError::MismatchedResponse] is returned
+    pub fn transfer_data(
+        &mut self,
+        block_sequence_counter: u8,
+        data: &[u8],
+    ) -> DiagServerResult<Vec<u8>> {
+        let mut args = Vec::with_capacity(data.len() + 1);
+        args.push(block_sequence_counter);
+        args.extend_from_slice(data);
+        let res = self.execute_command_with_response(UDSCommand::TransferData, &args)?;
+        if res.len() < 2 {
+            // Require Positive SID, block sequence counter
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if res[1] != block_sequence_counter {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected block sequence counter 0x{:02X}, got 0x{:02X}",
+                block_sequence_counter, res[1]
+            )));
+        }
+        Ok(res[2..].to_vec())
+    }
+
+    /// Downloads a complete data buffer to the ECU using multiple TransferData requests.
+    /// [UdsDiagnosticServer::request_download] must have been called prior to this function.
+    /// This function does NOT call [UdsDiagnosticServer::request_transfer_exit].
+    ///
+    /// ## Parameters
+    /// * max_block_len - maxNumberOfBlockLength returned by [UdsDiagnosticServer::request_download]
+    /// * data - The data to download
+    /// * on_progress - Called after each block with the number of bytes sent so far and the total number of bytes
+    pub fn transfer_data_blocks<F>(
+        &mut self,
+        max_block_len: u32,
+        data: &[u8],
+        mut on_progress: F,
+    ) -> DiagServerResult<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        // maxNumberOfBlockLength includes the SID and block sequence counter
+        if max_block_len <= 2 {
+            return Err(DiagError::ParameterInvalid);
+        }
+        let block_size = max_block_len as usize - 2;
+        let mut counter = BlockSequenceCounter::new();
+        let mut sent = 0;
+        for block in data.chunks(block_size) {
+            self.transfer_data(counter.get(), block)?;
+            counter.advance();
+            sent += block.len();
+            on_progress(sent, data.len());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod transfer_data_test {
+    use super::BlockSequenceCounter;
+
+    #[test]
+    fn test_block_sequence_counter_wrap() {
+        let mut counter = BlockSequenceCounter::new();
+        assert_eq!(counter.get(), 0x01);
+        for _ in 0..0xFE {
+            counter.advance();
+        }
+        assert_eq!(counter.get(), 0xFF);
+        counter.advance();
+        assert_eq!(counter.get(), 0x00);
+    }
+}
@@ -0,0 +1,64 @@
+//! Provides methods for writing data to the ECU using 16 bit data identifiers (DIDs)
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{UDSCommand, UdsDiagnosticServer};
+
+impl UdsDiagnosticServer {
+    /// Writes a data record to a DID on the ECU
+    ///
+    /// ## Parameters
+    /// * did - The 16 bit data identifier to write to
+    /// * data - The data record to write
+    ///
+    /// ## Returns
+    /// If the ECU's positive response does not echo back the same DID, then
+    /// [DiagError::MismatchedResponse] is returned
+    pub fn write_data_by_identifier(&mut self, did: u16, data: &[u8]) -> DiagServerResult<()> {
+        let mut args = Vec::with_capacity(data.len() + 2);
+        args.push((did >> 8) as u8);
+        args.push(did as u8);
+        args.extend_from_slice(data);
+        let res = self.execute_command_with_response(UDSCommand::WriteDataByIdentifier, &args)?;
+        if res.len() < 3 {
+            // Require Positive SID, DID << 8, DID & 0xFF
+            return Err(DiagError::InvalidResponseLength);
+        }
+        let did_response = (res[1] as u16) << 8 | res[2] as u16;
+        if did_response != did {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected identifier 0x{:04X}, got identifier 0x{:04X}",
+                did, did_response
+            )));
+        }
+        Ok(())
+    }
+
+    /// Writes a data record to a DID on the ECU, then reads the DID back using
+    /// [UdsDiagnosticServer::read_data_by_identifier] to verify the ECU stored the data.
+    ///
+    /// NOTE: This only works for DIDs which can be both read and written. Some ECUs only
+    /// apply written values after a reset, in which case the read back data will not match.
+    ///
+    /// ## Parameters
+    /// * did - The 16 bit data identifier to write to
+    /// * data - The data record to write
+    ///
+    /// ## Returns
+    /// If the read back data does not match the written data, then [DiagError::MismatchedResponse] is returned
+    pub fn write_data_by_identifier_verified(
+        &mut self,
+        did: u16,
+        data: &[u8],
+    ) -> DiagServerResult<()> {
+        self.write_data_by_identifier(did, data)?;
+        let read_back = self.read_data_by_identifier(did)?;
+        if read_back != data {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Identifier 0x{:04X} read back as {:02X?}, expected {:02X?}",
+                did, read_back, data
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+//! Provides methods for writing memory on the ECU by address with UDS
+
+use crate::{DiagError, DiagServerResult, DiagnosticServer};
+
+use super::{AddressAndLengthFormatIdentifier, UDSCommand, UdsDiagnosticServer};
+
+impl UdsDiagnosticServer {
+    /// Writes data to a memory region on the ECU
+    ///
+    /// ## Parameters
+    /// * alfid - Address and length format identifier, describing how many bytes
+    ///   are used to encode `address` and the size of `data`
+    /// * address - Memory address to write to
+    /// * data - Data to write
+    ///
+    /// ## Returns
+    /// If the ECU's positive response does not echo back the same address and size, then
+    /// [DiagError::MismatchedResponse] is returned
+    pub fn write_memory_by_address(
+        &mut self,
+        alfid: AddressAndLengthFormatIdentifier,
+        address: u64,
+        data: &[u8],
+    ) -> DiagServerResult<()> {
+        let mut args = alfid.encode(address, data.len() as u64)?;
+        let echo_len = args.len();
+        args.extend_from_slice(data);
+        let res = self.execute_command_with_response(UDSCommand::WriteMemoryByAddress, &args)?;
+        if res.len() != echo_len + 1 {
+            return Err(DiagError::InvalidResponseLength);
+        }
+        if res[1..] != args[..echo_len] {
+            return Err(DiagError::MismatchedResponse(format!(
+                "Expected address and size {:02X?}, got {:02X?}",
+                &args[..echo_len],
+                &res[1..]
+            )));
+        }
+        Ok(())
+    }
+
+    /// Writes data to a memory region on the ECU which is larger than a single request allows,
+    /// by splitting it into multiple [UdsDiagnosticServer::write_memory_by_address] requests.
+    ///
+    /// ## Parameters
+    /// * alfid - Address and length format identifier
+    /// * address - Start address of the memory region
+    /// * data - Data to write
+    /// * chunk_size - Maximum number of bytes to write per request
+    /// * on_progress - Called after each request with the number of bytes written so far and the total number of bytes
+    ///
+    /// ## Returns
+    /// [DiagError::ParameterInvalid] is returned if `chunk_size` is 0, or the memory region
+    /// cannot be addressed using `alfid`
+    pub fn write_memory_region<P>(
+        &mut self,
+        alfid: AddressAndLengthFormatIdentifier,
+        address: u64,
+        data: &[u8],
+        chunk_size: usize,
+        mut on_progress: P,
+    ) -> DiagServerResult<()>
+    where
+        P: FnMut(usize, usize),
+    {
+        if chunk_size == 0 {
+            return Err(DiagError::ParameterInvalid);
+        }
+        alfid.check_region(address, data.len() as u64)?;
+        let mut written = 0;
+        for chunk in data.chunks(chunk_size) {
+            self.write_memory_by_address(alfid, address + written as u64, chunk)?;
+            written += chunk.len();
+            on_progress(written, data.len());
+        }
+        Ok(())
+    }
+}